@@ -0,0 +1,265 @@
+//! Plays two bot executables against each other over the match protocol, like the arena does.
+//!
+//! Usage: `referee [options] "<bot 0 command>" "<bot 1 command>"`, where a command is an executable
+//! followed by its arguments, separated by spaces.
+
+use std::fmt;
+use std::io::{self, BufRead, BufReader, Write};
+use std::process::{self, Child, ChildStdin, ExitCode, Stdio};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::Duration;
+
+use infinibattle_2022::board_state::{BoardMove, BoardState, IllegalMove};
+use infinibattle_2022::protocol::{Command, MatchConfig, Message};
+
+#[derive(Debug)]
+struct Options {
+    commands: [String; 2],
+    max_turns: u32,
+    turn_timeout: Duration,
+    sleep: bool,
+}
+
+impl Options {
+    fn from_args(mut args: impl Iterator<Item = String>) -> Result<Options, String> {
+        let mut commands = vec![];
+        let mut max_turns = 100;
+        let mut turn_timeout = Duration::from_secs(2);
+        let mut sleep = true;
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--max-turns" => {
+                    let value = args.next().ok_or("missing value for --max-turns")?;
+                    max_turns = value.parse().map_err(|err| format!("invalid value \"{}\" for --max-turns: {}", value, err))?;
+                },
+                "--turn-timeout-ms" => {
+                    let value = args.next().ok_or("missing value for --turn-timeout-ms")?;
+                    turn_timeout = Duration::from_millis(value.parse().map_err(|err| format!("invalid value \"{}\" for --turn-timeout-ms: {}", value, err))?);
+                },
+                "--no-sleep" => sleep = false,
+                _ if arg.starts_with("--") => return Err(format!("unknown argument \"{}\"", arg)),
+                _ => commands.push(arg),
+            }
+        }
+
+        let commands: [String; 2] = commands.try_into().map_err(|_| "expected two bot commands")?;
+
+        Ok(Options { commands, max_turns, turn_timeout, sleep })
+    }
+}
+
+/// Why a bot lost without being trapped.
+#[derive(Debug)]
+enum Forfeit {
+    Io(io::Error),
+    Timeout,
+    Exited,
+    UnexpectedLine(String),
+    InvalidJson(String),
+    IllegalMove(BoardMove, IllegalMove),
+}
+
+impl fmt::Display for Forfeit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Forfeit::Io(err) => write!(f, "i/o error: {}", err),
+            Forfeit::Timeout => write!(f, "timed out"),
+            Forfeit::Exited => write!(f, "exited"),
+            Forfeit::UnexpectedLine(line) => write!(f, "sent unexpected line \"{}\"", line),
+            Forfeit::InvalidJson(line) => write!(f, "sent invalid move \"{}\"", line),
+            Forfeit::IllegalMove(board_move, reason) => write!(f, "played illegal move {}: {}", board_move.to_json(), reason),
+        }
+    }
+}
+
+impl From<io::Error> for Forfeit {
+    fn from(err: io::Error) -> Self {
+        Forfeit::Io(err)
+    }
+}
+
+/// A running bot, with its output lines read on a separate thread so reads can time out.
+struct BotProcess {
+    child: Child,
+    stdin: ChildStdin,
+    lines: Receiver<String>,
+}
+
+impl BotProcess {
+    fn spawn(command: &str) -> io::Result<BotProcess> {
+        let mut parts = command.split_whitespace();
+        let program = parts.next().ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "empty bot command"))?;
+
+        let mut child = process::Command::new(program).args(parts).stdin(Stdio::piped()).stdout(Stdio::piped()).spawn()?;
+        let stdin = child.stdin.take().unwrap();
+        let stdout = child.stdout.take().unwrap();
+
+        let (sender, lines) = mpsc::channel();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+
+        Ok(BotProcess { child, stdin, lines })
+    }
+
+    fn send(&mut self, messages: &[Message]) -> Result<(), Forfeit> {
+        for message in messages {
+            writeln!(self.stdin, "{}", message)?;
+        }
+        self.stdin.flush()?;
+        Ok(())
+    }
+
+    fn receive(&mut self, timeout: Duration) -> Result<String, Forfeit> {
+        match self.lines.recv_timeout(timeout) {
+            Ok(line) => Ok(line.trim_end().to_string()),
+            Err(RecvTimeoutError::Timeout) => Err(Forfeit::Timeout),
+            Err(RecvTimeoutError::Disconnected) => Err(Forfeit::Exited),
+        }
+    }
+
+    fn expect(&mut self, expected: Command, timeout: Duration) -> Result<(), Forfeit> {
+        let line = self.receive(timeout)?;
+        if Command::parse(&line) == Some(expected) {
+            Ok(())
+        } else {
+            Err(Forfeit::UnexpectedLine(line))
+        }
+    }
+
+    /// Asks the bot for its move in `board_state`, which must be its turn.
+    fn play_turn(&mut self, board_state: &BoardState, timeout: Duration) -> Result<BoardMove, Forfeit> {
+        self.send(&[Message::TurnInit(board_state.clone()), Message::TurnStart])?;
+
+        let line = self.receive(timeout)?;
+        let board_move = match Command::parse(&line) {
+            Some(Command::PlacePieces(board_move)) => board_move,
+            _ => return Err(Forfeit::InvalidJson(line)),
+        };
+
+        self.expect(Command::TurnEnd, timeout)?;
+
+        match board_state.validate_move(&board_move) {
+            Ok(()) => Ok(board_move),
+            Err(reason) => Err(Forfeit::IllegalMove(board_move, reason)),
+        }
+    }
+}
+
+impl Drop for BotProcess {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+#[derive(Debug)]
+enum GameResult {
+    Trapped { winner: usize },
+    Forfeit { winner: usize, reason: Forfeit },
+    TurnLimit { winner: Option<usize> },
+}
+
+/// Plays one game, logging every move to stdout.
+fn play_game(bots: &mut [BotProcess; 2], options: &Options) -> (GameResult, BoardState) {
+    let mut board_state = BoardState::new();
+
+    for (player, bot) in bots.iter_mut().enumerate() {
+        let setup = bot.expect(Command::BotStart, options.turn_timeout).and_then(|_| {
+            let match_config = MatchConfig { player: Some(player as u8), initial_board: Some(board_state.clone()), max_turns: Some(options.max_turns), turn_timeout: Some(options.turn_timeout), ..MatchConfig::default() };
+            bot.send(&[Message::GameInit(match_config), Message::GameStart])
+        });
+        if let Err(reason) = setup {
+            return (GameResult::Forfeit { winner: 1 - player, reason }, board_state);
+        }
+    }
+
+    while board_state.turn() <= options.max_turns {
+        let turn = board_state.turn();
+        let player = board_state.current_player() as usize - 1;
+
+        if board_state.legal_moves().next().is_none() {
+            return (GameResult::Trapped { winner: 1 - player }, board_state);
+        }
+
+        if options.sleep {
+            if let Err(reason) = bots[1 - player].send(&[Message::Sleep]) {
+                return (GameResult::Forfeit { winner: player, reason }, board_state);
+            }
+        }
+
+        match bots[player].play_turn(&board_state, options.turn_timeout) {
+            Ok(board_move) => {
+                board_state = board_state.apply_move(&board_move);
+                println!("turn {}: player {} plays {}, scores {:?}", turn, player, board_move.to_json(), board_state.scores());
+            },
+            Err(reason) => return (GameResult::Forfeit { winner: 1 - player, reason }, board_state),
+        }
+    }
+
+    let (score_player_0, score_player_1) = board_state.scores();
+    let winner = match score_player_0.cmp(&score_player_1) {
+        std::cmp::Ordering::Greater => Some(0),
+        std::cmp::Ordering::Less => Some(1),
+        std::cmp::Ordering::Equal => None,
+    };
+
+    (GameResult::TurnLimit { winner }, board_state)
+}
+
+fn main() -> ExitCode {
+    let options = match Options::from_args(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(err) => {
+            eprintln!("{}", err);
+            eprintln!("usage: referee [--max-turns <n>] [--turn-timeout-ms <ms>] [--no-sleep] \"<bot 0 command>\" \"<bot 1 command>\"");
+            return ExitCode::from(2);
+        },
+    };
+
+    let spawn = |command: &String| BotProcess::spawn(command).map_err(|err| format!("cannot start \"{}\": {}", command, err));
+    let mut bots = match (spawn(&options.commands[0]), spawn(&options.commands[1])) {
+        (Ok(bot_0), Ok(bot_1)) => [bot_0, bot_1],
+        (Err(err), _) | (_, Err(err)) => {
+            eprintln!("{}", err);
+            return ExitCode::from(2);
+        },
+    };
+
+    let (result, board_state) = play_game(&mut bots, &options);
+
+    println!("final scores {:?}", board_state.scores());
+    match result {
+        GameResult::Trapped { winner } => println!("player {} wins: player {} is trapped", winner, 1 - winner),
+        GameResult::Forfeit { winner, reason } => println!("player {} wins: player {} {}", winner, 1 - winner, reason),
+        GameResult::TurnLimit { winner: Some(winner) } => println!("player {} wins on score after {} turns", winner, options.max_turns),
+        GameResult::TurnLimit { winner: None } => println!("draw after {} turns", options.max_turns),
+    }
+
+    ExitCode::SUCCESS
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn options_from_args() {
+        let args = |args: &[&str]| Options::from_args(args.iter().map(|arg| arg.to_string()));
+
+        let options = args(&["--max-turns", "20", "bot --strategy random", "--no-sleep", "bot"]).unwrap();
+
+        assert_eq!(options.commands, ["bot --strategy random".to_string(), "bot".to_string()]);
+        assert_eq!(options.max_turns, 20);
+        assert!(!options.sleep);
+        assert!(args(&["bot"]).is_err());
+        assert!(args(&["bot", "bot", "bot"]).is_err());
+        assert!(args(&["--fast", "bot", "bot"]).is_err());
+    }
+}
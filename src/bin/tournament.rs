@@ -0,0 +1,201 @@
+//! Round-robin tournament between strategies, played in-process, with Elo estimates and optional SPRT.
+//!
+//! Usage: `tournament [options] <engine> <engine>...`, where an engine is a strategy name with an optional
+//! thinking time per move in milliseconds, like `minimax@100`.
+
+use std::process::ExitCode;
+use std::sync::Arc;
+use std::time::Duration;
+
+use infinibattle_2022::strategy::{SearchLimits, StrategyKind};
+use infinibattle_2022::tablebase::Tablebase;
+use infinibattle_2022::tournament::{play_game, MatchStats, Sprt, SprtDecision};
+
+#[derive(Clone, Debug, PartialEq)]
+struct Engine {
+    name: String,
+    kind: StrategyKind,
+    limits: SearchLimits,
+}
+
+impl Engine {
+    fn parse(spec: &str) -> Result<Engine, String> {
+        let (kind, limits) = match spec.split_once('@') {
+            Some((kind, millis)) => {
+                let millis = millis.parse().map_err(|err| format!("invalid time in engine \"{}\": {}", spec, err))?;
+                (kind, SearchLimits { time: Duration::from_millis(millis), ..SearchLimits::default() })
+            },
+            None => (spec, SearchLimits::default()),
+        };
+
+        Ok(Engine { name: spec.to_string(), kind: kind.parse()?, limits })
+    }
+}
+
+#[derive(Debug)]
+struct Options {
+    engines: Vec<Engine>,
+    games: u32,
+    max_turns: u32,
+    seed: u64,
+    sprt: Option<Sprt>,
+    tablebase_path: Option<String>,
+}
+
+impl Options {
+    fn from_args(mut args: impl Iterator<Item = String>) -> Result<Options, String> {
+        let mut options = Options { engines: vec![], games: 100, max_turns: 100, seed: 0, sprt: None, tablebase_path: None };
+
+        while let Some(arg) = args.next() {
+            let mut value = |name: &str| args.next().ok_or(format!("missing value for {}", name));
+            let invalid = |name: &str, value: &str| format!("invalid value \"{}\" for {}", value, name);
+
+            match arg.as_str() {
+                "--games" => {
+                    let games = value("--games")?;
+                    options.games = games.parse().map_err(|_| invalid("--games", &games))?;
+                },
+                "--max-turns" => {
+                    let max_turns = value("--max-turns")?;
+                    options.max_turns = max_turns.parse().map_err(|_| invalid("--max-turns", &max_turns))?;
+                },
+                "--seed" => {
+                    let seed = value("--seed")?;
+                    options.seed = seed.parse().map_err(|_| invalid("--seed", &seed))?;
+                },
+                "--sprt" => {
+                    let bounds = value("--sprt")?;
+                    let (elo0, elo1) = bounds
+                        .split_once(',')
+                        .and_then(|(elo0, elo1)| Some((elo0.parse().ok()?, elo1.parse().ok()?)))
+                        .ok_or_else(|| invalid("--sprt", &bounds))?;
+                    options.sprt = Some(Sprt::new(elo0, elo1));
+                },
+                "--tablebase" => options.tablebase_path = Some(value("--tablebase")?),
+                _ if arg.starts_with("--") => return Err(format!("unknown argument \"{}\"", arg)),
+                _ => options.engines.push(Engine::parse(&arg)?),
+            }
+        }
+
+        if options.engines.len() < 2 {
+            return Err("expected at least two engines".to_string());
+        }
+        if options.sprt.is_some() && options.engines.len() != 2 {
+            return Err("--sprt compares exactly two engines".to_string());
+        }
+
+        Ok(options)
+    }
+}
+
+fn print_stats(name: &str, opponent: &str, stats: &MatchStats) {
+    let elo = match stats.elo() {
+        Some((elo, lower, upper)) => format!("Elo {:.1} [{:.1}, {:.1}]", elo, lower, upper),
+        None => "no games".to_string(),
+    };
+    println!("{} vs {}: +{} -{} ={}, score differential {:+}, {}", name, opponent, stats.wins, stats.losses, stats.draws, stats.score_differential, elo);
+}
+
+fn main() -> ExitCode {
+    let options = match Options::from_args(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(err) => {
+            eprintln!("{}", err);
+            eprintln!("usage: tournament [--games <n>] [--max-turns <n>] [--seed <n>] [--sprt <elo0>,<elo1>] [--tablebase <path>] <engine>[@<ms>] <engine>[@<ms>]...");
+            return ExitCode::from(2);
+        },
+    };
+
+    let tablebase = if options.engines.iter().any(|engine| engine.kind.uses_tablebase()) {
+        let tablebase = match &options.tablebase_path {
+            Some(path) => match Tablebase::load_or_generate(path) {
+                Ok(tablebase) => tablebase,
+                Err(err) => {
+                    eprintln!("cannot load tablebase \"{}\": {}", path, err);
+                    return ExitCode::from(2);
+                },
+            },
+            None => Tablebase::generate(),
+        };
+        Some(Arc::new(tablebase))
+    } else {
+        None
+    };
+
+    let engines = &options.engines;
+    let mut totals = vec![MatchStats::default(); engines.len()];
+    let mut game_number = 0u64;
+
+    for first in 0..engines.len() {
+        for second in first + 1..engines.len() {
+            let mut stats = MatchStats::default();
+
+            for game in 0..options.games {
+                // alternate who is player 0, and therefore moves first
+                let seats = if game % 2 == 0 { [first, second] } else { [second, first] };
+                let seed = options.seed.wrapping_add(game_number * 2);
+                game_number += 1;
+
+                let mut strategy_0 = engines[seats[0]].kind.create(tablebase.clone(), seed);
+                let mut strategy_1 = engines[seats[1]].kind.create(tablebase.clone(), seed.wrapping_add(1));
+                let record = play_game([strategy_0.as_mut(), strategy_1.as_mut()], [&engines[seats[0]].limits, &engines[seats[1]].limits], options.max_turns);
+
+                stats.add(&record, if seats[0] == first { 0 } else { 1 });
+
+                if let Some(sprt) = &options.sprt {
+                    if let Some(decision) = sprt.decision(&stats) {
+                        let (lower, upper) = sprt.bounds();
+                        let verdict = match decision {
+                            SprtDecision::AcceptH1 => format!("H1 accepted: {} is at least {} Elo stronger", engines[first].name, sprt.elo1),
+                            SprtDecision::AcceptH0 => format!("H0 accepted: {} is at most {} Elo stronger", engines[first].name, sprt.elo0),
+                        };
+                        println!("SPRT after {} games: LLR {:.2} [{:.2}, {:.2}], {}", stats.games(), sprt.llr(&stats), lower, upper, verdict);
+                        break;
+                    }
+                }
+            }
+
+            print_stats(&engines[first].name, &engines[second].name, &stats);
+            totals[first] += stats;
+            totals[second] += stats.reversed();
+        }
+    }
+
+    if engines.len() > 2 {
+        println!();
+        let mut standings: Vec<usize> = (0..engines.len()).collect();
+        standings.sort_by_key(|engine| std::cmp::Reverse(2 * totals[*engine].wins + totals[*engine].draws));
+        for engine in standings {
+            print_stats(&engines[engine].name, "the field", &totals[engine]);
+        }
+    }
+
+    ExitCode::SUCCESS
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn engine_from_spec() {
+        assert_eq!(Engine::parse("mcts@50").unwrap().limits.time, Duration::from_millis(50));
+        assert_eq!(Engine::parse("random").unwrap().kind, StrategyKind::Random);
+        assert!(Engine::parse("mcts@fast").is_err());
+        assert!(Engine::parse("alphazero").is_err());
+    }
+
+    #[test]
+    fn options_from_args() {
+        let args = |args: &[&str]| Options::from_args(args.iter().map(|arg| arg.to_string()));
+
+        let options = args(&["--games", "10", "--sprt", "0,10", "minimax@20", "greedy"]).unwrap();
+
+        assert_eq!(options.games, 10);
+        assert_eq!(options.sprt, Some(Sprt::new(0.0, 10.0)));
+        assert_eq!(options.engines.len(), 2);
+        assert!(args(&["greedy"]).is_err());
+        assert!(args(&["--sprt", "0,10", "greedy", "random", "mcts"]).is_err());
+        assert!(args(&["--sprt", "10", "greedy", "random"]).is_err());
+    }
+}
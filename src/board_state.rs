@@ -1,56 +1,342 @@
-use json;
+use std::fmt;
+use std::time::Duration;
+
+use crate::search::Search;
+use crate::symmetry::Symmetry;
+use crate::tablebase::Tablebase;
+use crate::zobrist;
+
+/// The 8 orientations of the L-piece, as offsets from the square in its corner.
+const L_PIECE_ORIENTATIONS: [[(i32, i32); 4]; 8] = [
+//023
+//1
+    [(0,0), (0,1), (1,0), (2,0)],
+//10
+// 2
+// 3
+    [(0,0), (-1,0), (0,1), (0,2)],
+//  1
+//320
+    [(0,0), (0,-1), (-1,0), (-2,0)],
+// 3
+// 2
+// 01
+    [(0,0), (1,0), (0,-1), (0,-2)],
+//1
+//023
+    [(0,0), (0,-1), (1,0), (2,0)],
+// 3
+// 2
+//10
+    [(0,0), (-1,0), (0,-1), (0,-2)],
+//320
+//  1
+    [(0,0), (0,1), (-1,0), (-2,0)],
+// 01
+// 2
+// 3
+    [(0,0), (1,0), (0,1), (0,2)],
+];
+
+/// Bit of the square (x, y) in a board mask; squares are numbered row by row, like the JSON board.
+const fn square_bit(x: i32, y: i32) -> u16 {
+    1 << (y * 4 + x)
+}
+
+/// The squares of the corners, where the L-piece scores.
+pub const CORNERS: u16 = square_bit(0, 0) | square_bit(3, 0) | square_bit(0, 3) | square_bit(3, 3);
+
+const ALL_SQUARES: u16 = u16::MAX;
+
+const fn l_piece_placements() -> [[[i32; 2]; 4]; 48] {
+    let mut placements = [[[0; 2]; 4]; 48];
+    let mut count = 0;
+    let mut square = 0;
+    while square < 16 {
+        let mut orientation = 0;
+        while orientation < 8 {
+            let mut placement = [[0; 2]; 4];
+            let mut fits = true;
+            let mut i = 0;
+            while i < 4 {
+                let (dx, dy) = L_PIECE_ORIENTATIONS[orientation][i];
+                let (x, y) = (square % 4 + dx, square / 4 + dy);
+                fits &= x >= 0 && x < 4 && y >= 0 && y < 4;
+                placement[i] = [x, y];
+                i += 1;
+            }
+            if fits {
+                placements[count] = placement;
+                count += 1;
+            }
+            orientation += 1;
+        }
+        square += 1;
+    }
+    placements
+}
+
+/// Mask of the squares at `positions` that are on the board.
+const fn positions_mask(positions: &[[i32; 2]]) -> u16 {
+    let mut mask = 0;
+    let mut i = 0;
+    while i < positions.len() {
+        let [x, y] = positions[i];
+        if x >= 0 && x < 4 && y >= 0 && y < 4 {
+            mask |= square_bit(x, y);
+        }
+        i += 1;
+    }
+    mask
+}
+
+const fn l_piece_masks() -> [u16; 48] {
+    let mut masks = [0; 48];
+    let mut i = 0;
+    while i < 48 {
+        masks[i] = positions_mask(&L_PIECE_PLACEMENTS[i]);
+        i += 1;
+    }
+    masks
+}
+
+/// All 48 ways to place an L-piece on an empty board, by the square of its corner and then by orientation.
+const L_PIECE_PLACEMENTS: [[[i32; 2]; 4]; 48] = l_piece_placements();
+
+/// `L_PIECE_PLACEMENTS` as masks.
+pub const L_PIECE_MASKS: [u16; 48] = l_piece_masks();
+
+fn square_position(square: u32) -> [i32; 2] {
+    [(square % 4) as i32, (square / 4) as i32]
+}
+
+/// The squares in `mask`, in ascending order.
+fn squares(mut mask: u16) -> impl Iterator<Item = u32> {
+    std::iter::from_fn(move || {
+        if mask == 0 {
+            return None;
+        }
+        let square = mask.trailing_zeros();
+        mask &= mask - 1;
+        Some(square)
+    })
+}
 
-#[derive(Debug, PartialEq, Eq)]
+fn transform_mask(mask: u16, symmetry: Symmetry) -> u16 {
+    squares(mask).fold(0, |transformed, square| transformed | 1 << symmetry.apply_to_square(square as usize))
+}
+
+/// A position as three bitboards, with bit `y * 4 + x` for the square (x, y).
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct BoardState {
-    board: [u8; 16],
+    /// The squares of player 1's and player 2's L-piece.
+    l_pieces: [u16; 2],
+    neutral_pieces: u16,
     current_player: u8,
-    scores: (i32, i32)
+    scores: (i32, i32),
+    /// The turn the next move is played in, counting from 1 like the "Turn" field of the arena.
+    turn: u32,
+    /// The last turn of the game, after which the scores decide; `None` if unknown.
+    max_turns: Option<u32>,
+}
+
+/// Player 1 is boxed in, and player 2, to move, can keep it that way and win.
+#[cfg(test)]
+pub(crate) const TRAPPING_BOARD: [u8; 16] = [0, 0, 4, 0,
+                                             2, 2, 2, 0,
+                                             1, 0, 2, 0,
+                                             1, 1, 1, 4];
+
+/// `TRAPPING_BOARD` with player 2 to move and no scores yet.
+#[cfg(test)]
+pub(crate) fn trapping_position() -> BoardState {
+    BoardState::from_board(TRAPPING_BOARD, 2, (0, 0))
+}
+
+impl Default for BoardState {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl BoardState {
     pub fn new() -> BoardState {
-        BoardState { board: [4, 0, 0, 0, 1, 2, 2, 2, 1, 1, 1, 2, 0, 0, 0, 4], current_player: 1, scores: (0,0) }
+        BoardState::from_board([4, 0, 0, 0, 1, 2, 2, 2, 1, 1, 1, 2, 0, 0, 0, 4], 1, (0,0))
+    }
+
+    /// A position from the 16 squares of the board (row by row), the player to move and the scores, in turn 1.
+    pub fn from_board(board: [u8; 16], current_player: u8, scores: (i32, i32)) -> BoardState {
+        let mask = |value| (0..16).filter(|square| board[*square] == value).fold(0, |mask, square| mask | 1 << square);
+        BoardState::from_bitboards([mask(1), mask(2)], mask(4), current_player, scores)
+    }
+
+    /// A position from the masks of both L-pieces and of the neutral pieces, in turn 1.
+    pub fn from_bitboards(l_pieces: [u16; 2], neutral_pieces: u16, current_player: u8, scores: (i32, i32)) -> BoardState {
+        BoardState { l_pieces, neutral_pieces, current_player, scores, turn: 1, max_turns: None }
+    }
+
+    /// The same position in another turn.
+    pub fn with_turn(mut self, turn: u32) -> BoardState {
+        self.turn = turn;
+        self
+    }
+
+    /// The same position in a game that ends after `max_turns`.
+    pub fn with_max_turns(mut self, max_turns: Option<u32>) -> BoardState {
+        self.max_turns = max_turns;
+        self
+    }
+
+    pub fn board(&self) -> [u8; 16] {
+        let mut board = [0; 16];
+        for (value, mask) in [(1, self.l_pieces[0]), (2, self.l_pieces[1]), (4, self.neutral_pieces)] {
+            for square in squares(mask) {
+                board[square as usize] = value;
+            }
+        }
+        board
+    }
+
+    /// Masks of player 1's and player 2's L-piece.
+    pub fn l_pieces(&self) -> [u16; 2] {
+        self.l_pieces
+    }
+
+    pub fn neutral_pieces(&self) -> u16 {
+        self.neutral_pieces
+    }
+
+    pub fn scores(&self) -> (i32, i32) {
+        self.scores
+    }
+
+    pub fn turn(&self) -> u32 {
+        self.turn
     }
 
-    pub fn load(board_state_json: json::JsonValue) -> BoardState {
-        let scores = (board_state_json["GameState"]["ScorePlayer0"].as_i32().unwrap(), board_state_json["GameState"]["ScorePlayer1"].as_i32().unwrap());
-        let current_player = board_state_json["Player"].as_u8().unwrap() + 1;
-        let mut board_state = BoardState { board: [0; 16], current_player, scores };
+    pub fn max_turns(&self) -> Option<u32> {
+        self.max_turns
+    }
+
+    /// Number of moves left to play, including this one; 0 when the game is over and the scores decide.
+    /// `None` without a turn limit.
+    pub fn remaining_turns(&self) -> Option<u32> {
+        self.max_turns.map(|max_turns| (max_turns + 1).saturating_sub(self.turn))
+    }
+
+    /// All 48 ways to place an L-piece on an empty board.
+    pub fn l_piece_placements() -> Vec<[[i32; 2]; 4]> {
+        L_PIECE_PLACEMENTS.to_vec()
+    }
+
+    /// Reads a game state message of the arena, checking that it describes a position of the L game.
+    /// The "Turn" field may be left out, and is 1 then.
+    pub fn load(board_state_json: json::JsonValue) -> Result<BoardState, LoadError> {
+        let game_state = &board_state_json["GameState"];
+        let score_player_0 = game_state["ScorePlayer0"].as_i32().ok_or(LoadError::InvalidField("GameState.ScorePlayer0"))?;
+        let score_player_1 = game_state["ScorePlayer1"].as_i32().ok_or(LoadError::InvalidField("GameState.ScorePlayer1"))?;
+        let current_player = match board_state_json["Player"].as_u8() {
+            Some(player @ (0 | 1)) => player + 1,
+            _ => return Err(LoadError::InvalidField("Player")),
+        };
+
+        let rows = &game_state["Board"]["Board"];
+        if !rows.is_array() {
+            return Err(LoadError::InvalidField("GameState.Board.Board"));
+        }
+        if rows.len() != 4 || rows.members().any(|row| !row.is_array() || row.len() != 4) {
+            return Err(LoadError::BoardSize);
+        }
+
+        let mut board = [0; 16];
         for y in 0..4 {
             for x in 0..4 {
-                board_state.board[y*4+x] = board_state_json["GameState"]["Board"]["Board"][y][x].as_u8().unwrap();
+                board[y*4+x] = match rows[y][x].as_u8() {
+                    Some(value @ (0 | 1 | 2 | 4)) => value,
+                    _ => return Err(LoadError::InvalidSquare { x, y }),
+                };
             }
         }
-        board_state
-    }
 
-    fn get_value_at_pos(&self, x: i32, y: i32) -> Option<u8> {
-        if x < 0 || x >= 4 || y < 0 || y >= 4 {
-            None
-        } else {
-            Some(self.board[(y*4+x) as usize])
+        let turn = match &board_state_json["Turn"] {
+            json::JsonValue::Null => 1,
+            turn => turn.as_u32().ok_or(LoadError::InvalidField("Turn"))?,
+        };
+
+        let board_state = BoardState::from_board(board, current_player, (score_player_0, score_player_1)).with_turn(turn);
+
+        for (piece, mask, expected) in [(1, board_state.l_pieces[0], 4), (2, board_state.l_pieces[1], 4), (4, board_state.neutral_pieces, 2)] {
+            if mask.count_ones() != expected {
+                return Err(LoadError::PieceCount { piece, count: mask.count_ones() });
+            }
+        }
+        for (player, mask) in [(1, board_state.l_pieces[0]), (2, board_state.l_pieces[1])] {
+            if !L_PIECE_MASKS.contains(&mask) {
+                return Err(LoadError::NotAnLShape { player });
+            }
         }
+
+        Ok(board_state)
     }
 
-    fn get_neutral_piece_positions(&self) -> Vec<[i32; 2]>
-    {
-        self.get_positions_with_value(4)
+    /// The game state as the arena sends it.
+    pub fn to_json(&self) -> json::JsonValue {
+        let rows: Vec<Vec<u8>> = self.board().chunks(4).map(|row| row.to_vec()).collect();
+        json::object! {
+            "GameState": {
+                "Board": { "Board": rows },
+                "ScorePlayer0": self.scores.0,
+                "ScorePlayer1": self.scores.1
+            },
+            "Turn": self.turn,
+            "Player": self.current_player - 1
+        }
     }
 
-    fn get_positions_with_value(&self, value: u8) -> Vec<[i32; 2]>
-    {
-        let mut positions = vec![];
-        for y in 0..4i32 {
-            for x in 0..4i32 {
-                if self.get_value_at_pos(x,y) == Some(value) {
-                    positions.push([x,y]);
-                }
+    /// Zobrist hash of the board, the player to move, the scores and, with a turn limit, the turns left.
+    pub fn zobrist_hash(&self) -> u64 {
+        let mut hash = zobrist::score_key(1, self.scores.0) ^ zobrist::score_key(2, self.scores.1);
+
+        if let Some(remaining_turns) = self.remaining_turns() {
+            hash ^= zobrist::remaining_turns_key(remaining_turns);
+        }
+
+        if self.current_player == 2 {
+            hash ^= zobrist::SIDE_KEY;
+        }
+
+        for (piece, mask) in [self.l_pieces[0], self.l_pieces[1], self.neutral_pieces].into_iter().enumerate() {
+            for square in squares(mask) {
+                hash ^= zobrist::PIECE_KEYS[piece][square as usize];
             }
         }
-        positions
+
+        hash
+    }
+
+    /// The same position with the board transformed by `symmetry`.
+    pub fn transformed(&self, symmetry: Symmetry) -> BoardState {
+        BoardState {
+            l_pieces: self.l_pieces.map(|mask| transform_mask(mask, symmetry)),
+            neutral_pieces: transform_mask(self.neutral_pieces, symmetry),
+            current_player: self.current_player,
+            scores: self.scores,
+            turn: self.turn,
+            max_turns: self.max_turns,
+        }
     }
 
-    fn current_player(&self) -> u8 {
+    /// The representative of this position's symmetry class, and the symmetry that transforms this
+    /// position into it. Moves found for the canonical position map back with `symmetry.inverse()`.
+    pub fn canonical(&self) -> (BoardState, Symmetry) {
+        Symmetry::ALL
+            .into_iter()
+            .map(|symmetry| (self.transformed(symmetry), symmetry))
+            .min_by_key(|(board_state, _)| (board_state.l_pieces, board_state.neutral_pieces))
+            .unwrap()
+    }
+
+    pub fn current_player(&self) -> u8 {
         self.current_player
     }
 
@@ -58,179 +344,404 @@ impl BoardState {
         3 - self.current_player
     }
 
-    fn calculate_legal_moves(&self) -> Vec<BoardMove> {
-
-        let orientations: Vec<Vec<(i32, i32)>> = vec![
-        //023
-        //1
-        vec![(0,0), (0,1), (1,0), (2,0)],
-        //10
-        // 2
-        // 3
-        vec![(0,0), (-1,0), (0,1), (0,2)],
-        //  1
-        //320
-        vec![(0,0), (0,-1), (-1,0), (-2,0)],
-        // 3
-        // 2
-        // 01
-        vec![(0,0), (1,0), (0,-1), (0,-2)],
-        //1
-        //023
-        vec![(0,0), (0,-1), (1,0), (2,0)],
-        // 3
-        // 2
-        //10
-        vec![(0,0), (-1,0), (0,-1), (0,-2)],
-        //320
-        //  1
-        vec![(0,0), (0,1), (-1,0), (-2,0)],
-        // 01
-        // 2
-        // 3
-        vec![(0,0), (1,0), (0,1), (0,2)]
-        ];
-
-        let mut legal_moves = vec![];
-
-        let move_template = BoardMove{ lPiece: [[0,0], [0,0],[0,0],[0,0]], neutralPieces: [[0,0],[0,0]]};
-
-        for y in 0..4i32 {
-            for x in 0..4i32 {
-                if self.get_value_at_pos(x, y) == Some(0) || self.get_value_at_pos(x, y) == Some(self.current_player()) {
-
-                    for orientation in &orientations {
-                        // TODO: clean up, need iterators and correct types...
-                        let mut orientation_fits = true;
-                        for coord in orientation {
-                            let dx = coord.0 + x as i32;
-                            let dy = coord.1 + y as i32;
-                            let value = self.get_value_at_pos(dx, dy);
-                            if value == None || value == Some(4) || value == Some(self.other_player()) {
-                                orientation_fits = false;
-                            }
-                        }
-
-                        if orientation_fits {
-                            let mut new_move = move_template.clone();
-                            for (i, coord) in orientation.iter().enumerate() {
-                                let dx = coord.0 + x as i32;
-                                let dy = coord.1 + y as i32;
-                                new_move.lPiece[i][0] = dx;
-                                new_move.lPiece[i][1] = dy;
-                            }
-
-                            // filter "no move" of L-piece
-                            for [x,y] in new_move.lPiece {
-                                if self.get_value_at_pos(x, y) != Some(self.current_player()) {
-                                    let neutral_piece_positions = self.get_neutral_piece_positions();
-                                    new_move.neutralPieces[0] = neutral_piece_positions[0];
-                                    new_move.neutralPieces[1] = neutral_piece_positions[1];
-
-                                    // move without changing neutral pieces
-                                    legal_moves.push(new_move.clone());
-
-                                    // add legal neutral piece moves
-                                    let temp_board = self.apply_move(&new_move);
-
-                                    for i in 0..2 {
-                                        for y in 0..4i32 {
-                                            for x in 0..4i32 {
-                                                if temp_board.get_value_at_pos(x,y) == Some(0) {
-                                                    let mut new_move_with_neutral_piece = new_move.clone();
-                                                    new_move_with_neutral_piece.neutralPieces[i] = [x,y];
-                                                    legal_moves.push(new_move_with_neutral_piece);
-                                                }
-                                            }
-                                        }
-                                    }
-                                    break;
-                                }
-                            }
-                        }
-                    }
-                }
-            }
+    fn own_l_piece(&self) -> u16 {
+        self.l_pieces[self.current_player as usize - 1]
+    }
+
+    fn other_l_piece(&self) -> u16 {
+        self.l_pieces[self.other_player() as usize - 1]
+    }
+
+    /// The legal moves, each leading to a different position, generated without allocating.
+    pub fn legal_moves(&self) -> LegalMoves {
+        let mut neutral_squares = squares(self.neutral_pieces).map(square_position);
+        let neutral_pieces = [neutral_squares.next().unwrap_or_default(), neutral_squares.next().unwrap_or_default()];
+
+        LegalMoves {
+            own: self.own_l_piece(),
+            blocked: self.other_l_piece() | self.neutral_pieces,
+            neutral_pieces,
+            placement: 0,
+            moving_neutral_piece: None,
+            empty: 0,
         }
+    }
 
-        legal_moves
+    pub fn calculate_legal_moves(&self) -> Vec<BoardMove> {
+        self.legal_moves().collect()
     }
 
-    fn apply_move(&self, board_move: &BoardMove) -> Self {
+    /// Whether `board_move` is legal, regardless of the order of its squares.
+    pub fn is_legal_move(&self, board_move: &BoardMove) -> bool {
+        self.validate_move(board_move).is_ok()
+    }
 
-        let mut new_board = self.board.clone();
-        let mut new_scores = self.scores;
+    /// Checks `board_move` against the rules, regardless of the order of its squares, and returns the first rule it breaks.
+    pub fn validate_move(&self, board_move: &BoardMove) -> Result<(), IllegalMove> {
+        let on_board = |[x, y]: &[i32; 2]| (0..4).contains(x) && (0..4).contains(y);
+        if let Some(position) = board_move.l_piece.iter().chain(&board_move.neutral_pieces).find(|position| !on_board(position)) {
+            return Err(IllegalMove::OutOfBounds(*position));
+        }
 
-        // remove pieces from board
-        for y in 0..4 {
-            for x in 0..4 {
-                if new_board[y*4+x] == self.current_player() || new_board[y*4+x] == 4 {
-                    new_board[y*4+x] = 0;
-                }
-            }
+        let l_piece = positions_mask(&board_move.l_piece);
+        if !L_PIECE_MASKS.contains(&l_piece) {
+            return Err(IllegalMove::NotAnLShape);
+        }
+        if l_piece & self.other_l_piece() != 0 {
+            return Err(IllegalMove::LPieceOnOpponent);
+        }
+        if l_piece & self.neutral_pieces != 0 {
+            return Err(IllegalMove::LPieceOnNeutralPiece);
+        }
+        if l_piece == self.own_l_piece() {
+            return Err(IllegalMove::LPieceNotMoved);
         }
 
-        // replace pieces
-        for y in 0..4 {
-            for x in 0..4 {
-                if board_move.lPiece.iter().any(|[lx,ly]| *lx==x && *ly == y) {
-                    new_board[(y*4+x) as usize] = self.current_player();
-                }
-                if board_move.neutralPieces.iter().any(|[lx,ly]| *lx==x && *ly == y) {
-                    new_board[(y*4+x) as usize] = 4;
-                }
-            }
+        let neutral_pieces = positions_mask(&board_move.neutral_pieces);
+        if neutral_pieces.count_ones() != 2 {
+            return Err(IllegalMove::NeutralPiecesOnSameSquare);
+        }
+        if let Some(position) = board_move.neutral_pieces.iter().find(|position| positions_mask(&[**position]) & (l_piece | self.other_l_piece()) != 0) {
+            return Err(IllegalMove::NeutralPieceOnLPiece(*position));
         }
+        if (neutral_pieces & !self.neutral_pieces).count_ones() > 1 {
+            return Err(IllegalMove::BothNeutralPiecesMoved);
+        }
+
+        Ok(())
+    }
+
+    pub fn apply_move(&self, board_move: &BoardMove) -> Self {
+        let mut new_state = self.clone();
+        new_state.make_move(board_move);
+        new_state
+    }
 
-        // update scores
-        if new_board[0] == self.current_player()  ||
-           new_board[3] == self.current_player()  ||
-           new_board[12] == self.current_player() ||
-           new_board[15] == self.current_player() {
-            if self.current_player() == 1 {
-                new_scores.0 += 1
+    /// Plays `board_move` in place, and returns what `unmake_move` needs to take it back.
+    pub fn make_move(&mut self, board_move: &BoardMove) -> MoveUndo {
+        let undo = MoveUndo { l_pieces: self.l_pieces, neutral_pieces: self.neutral_pieces, scores: self.scores };
+
+        let player = self.current_player as usize - 1;
+        let l_piece = positions_mask(&board_move.l_piece);
+        let neutral_pieces = positions_mask(&board_move.neutral_pieces);
+
+        // pieces put down replace whatever was on their squares, and neutral pieces go on top
+        self.l_pieces[player] = l_piece & !neutral_pieces;
+        self.l_pieces[1 - player] &= !(l_piece | neutral_pieces);
+        self.neutral_pieces = neutral_pieces;
+
+        if self.l_pieces[player] & CORNERS != 0 {
+            if player == 0 {
+                self.scores.0 += 1
             } else {
-                new_scores.1 += 1
+                self.scores.1 += 1
             }
         }
 
-        BoardState{ board: new_board, current_player: if self.current_player() == 1 { 2 } else { 1 }, scores: new_scores }
+        self.current_player = self.other_player();
+        self.turn += 1;
+
+        undo
     }
 
-    fn evaluate(&self, depth: u32, player: u8) -> f32 {
-        let legal_moves = self.calculate_legal_moves();
+    /// Takes back the move that returned `undo`, which must be the last move made.
+    pub fn unmake_move(&mut self, undo: MoveUndo) {
+        self.l_pieces = undo.l_pieces;
+        self.neutral_pieces = undo.neutral_pieces;
+        self.scores = undo.scores;
+        self.current_player = self.other_player();
+        self.turn -= 1;
+    }
 
-        if depth == 0 || legal_moves.len() == 0 {
-            if player == 1 {
-                (self.scores.0 - self.scores.1) as f32
-            } else {
-                (self.scores.1 - self.scores.0) as f32
-            }
+    /// Score difference from the perspective of the player to move.
+    pub fn score_difference(&self) -> f32 {
+        if self.current_player() == 1 {
+            (self.scores.0 - self.scores.1) as f32
         } else {
+            (self.scores.1 - self.scores.0) as f32
+        }
+    }
+
+    /// The best move when searching `depth` plies beyond it, with `Search`; positions the tablebase solves are
+    /// played perfectly. `None` if there are no legal moves.
+    pub fn calculate_optimal_move(&self, depth: u32) -> Option<BoardMove> {
+        let mut search = Search::default();
+        search.tablebase = Some(Tablebase::shared());
+        search.max_depth = depth + 1;
+        search.iterative_deepening(self, Duration::MAX).map(|evaluation| evaluation.principal_variation[0].clone())
+    }
+}
+
+/// Iterator over the legal moves of a position, see `BoardState::legal_moves`.
+///
+/// For every L-piece placement that fits and differs from the current one, it yields the move that leaves the
+/// neutral pieces alone, then the moves of the first and of the second neutral piece to each empty square.
+#[derive(Clone, Debug)]
+pub struct LegalMoves {
+    own: u16,
+    blocked: u16,
+    neutral_pieces: [[i32; 2]; 2],
+    /// Index of the current placement in `L_PIECE_PLACEMENTS`.
+    placement: usize,
+    /// `None` before the move without a neutral piece move has been yielded.
+    moving_neutral_piece: Option<usize>,
+    /// Squares left to move the current neutral piece to.
+    empty: u16,
+}
+
+impl Iterator for LegalMoves {
+    type Item = BoardMove;
 
-            // IMPORTANT TODO: recurse
-            0.0
+    fn next(&mut self) -> Option<BoardMove> {
+        while self.placement < L_PIECE_MASKS.len() {
+            let mask = L_PIECE_MASKS[self.placement];
+
+            // the L-piece has to fit, and has to move
+            if mask & self.blocked != 0 || mask == self.own {
+                self.placement += 1;
+                continue;
+            }
+
+            let mut board_move = BoardMove { l_piece: L_PIECE_PLACEMENTS[self.placement], neutral_pieces: self.neutral_pieces };
+            let empty = ALL_SQUARES & !(mask | self.blocked);
+
+            match self.moving_neutral_piece {
+                None => {
+                    self.moving_neutral_piece = Some(0);
+                    self.empty = empty;
+                    return Some(board_move);
+                },
+                Some(i) if self.empty != 0 => {
+                    board_move.neutral_pieces[i] = square_position(self.empty.trailing_zeros());
+                    self.empty &= self.empty - 1;
+                    return Some(board_move);
+                },
+                Some(0) => {
+                    self.moving_neutral_piece = Some(1);
+                    self.empty = empty;
+                },
+                Some(_) => {
+                    self.moving_neutral_piece = None;
+                    self.placement += 1;
+                },
+            }
         }
+
+        None
     }
+}
+
+/// The part of a position that `BoardState::make_move` changes, besides the player to move.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MoveUndo {
+    l_pieces: [u16; 2],
+    neutral_pieces: u16,
+    scores: (i32, i32),
+}
+
+/// The rule a move breaks, see `BoardState::validate_move`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum IllegalMove {
+    /// A piece is placed outside the board.
+    OutOfBounds([i32; 2]),
+    /// The four squares of the L-piece don't form an L.
+    NotAnLShape,
+    /// The L-piece overlaps the opponent's L-piece.
+    LPieceOnOpponent,
+    /// The L-piece overlaps a neutral piece.
+    LPieceOnNeutralPiece,
+    /// The L-piece is put back on the squares it was on.
+    LPieceNotMoved,
+    NeutralPiecesOnSameSquare,
+    /// A neutral piece is placed on a square of either L-piece, after the L-piece moved.
+    NeutralPieceOnLPiece([i32; 2]),
+    /// Only one neutral piece may move.
+    BothNeutralPiecesMoved,
+}
 
-    fn evaluate_move(&self, board_move: &BoardMove, depth: u32, player: u8) -> f32 {
-        let new_board = self.apply_move(&board_move);
-        new_board.evaluate(depth, player)
+impl fmt::Display for IllegalMove {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IllegalMove::OutOfBounds([x, y]) => write!(f, "square ({}, {}) is not on the board", x, y),
+            IllegalMove::NotAnLShape => write!(f, "the L-piece is not an L"),
+            IllegalMove::LPieceOnOpponent => write!(f, "the L-piece overlaps the opponent's L-piece"),
+            IllegalMove::LPieceOnNeutralPiece => write!(f, "the L-piece overlaps a neutral piece"),
+            IllegalMove::LPieceNotMoved => write!(f, "the L-piece did not move"),
+            IllegalMove::NeutralPiecesOnSameSquare => write!(f, "both neutral pieces are on the same square"),
+            IllegalMove::NeutralPieceOnLPiece([x, y]) => write!(f, "neutral piece on ({}, {}) overlaps an L-piece", x, y),
+            IllegalMove::BothNeutralPiecesMoved => write!(f, "both neutral pieces moved"),
+        }
     }
+}
 
-    pub fn calculate_optimal_move(&self, depth: u32) -> Option<BoardMove> {
-        self.calculate_legal_moves()
-            .into_iter()
-            .max_by(|x,y| self.evaluate_move(&x, depth, self.current_player()).partial_cmp(&self.evaluate_move(&y, depth, self.current_player()))
-                                                .unwrap_or(std::cmp::Ordering::Equal))
+impl std::error::Error for IllegalMove {}
+
+/// Why a game state message is not a position, see `BoardState::load`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum LoadError {
+    /// A field is missing or has the wrong type, by its path in the message.
+    InvalidField(&'static str),
+    /// The board is not 4 rows of 4 squares.
+    BoardSize,
+    /// A square holds something other than 0 (empty), 1 or 2 (an L-piece) or 4 (a neutral piece).
+    InvalidSquare { x: usize, y: usize },
+    /// There are not 4 squares of each L-piece, or not 2 neutral pieces.
+    PieceCount { piece: u8, count: u32 },
+    NotAnLShape { player: u8 },
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoadError::InvalidField(field) => write!(f, "missing or invalid field {}", field),
+            LoadError::BoardSize => write!(f, "the board is not 4 by 4"),
+            LoadError::InvalidSquare { x, y } => write!(f, "invalid value on square ({}, {})", x, y),
+            LoadError::PieceCount { piece: 4, count } => write!(f, "expected 2 neutral pieces, found {}", count),
+            LoadError::PieceCount { piece, count } => write!(f, "expected 4 squares of L-piece {}, found {}", piece, count),
+            LoadError::NotAnLShape { player } => write!(f, "L-piece {} is not an L", player),
+        }
     }
 }
 
+impl std::error::Error for LoadError {}
+
+/// Score of a won position, minus the number of plies it takes to get there.
+pub const WIN_SCORE: f32 = 1000.0;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Evaluation {
+    pub score: f32,
+    pub principal_variation: Vec<BoardMove>
+}
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct BoardMove {
-    pub lPiece: [[i32; 2]; 4],
-    pub neutralPieces: [[i32; 2]; 2]
+    pub l_piece: [[i32; 2]; 4],
+    pub neutral_pieces: [[i32; 2]; 2]
+}
+
+impl BoardMove {
+    /// The place-pieces command of the match protocol.
+    pub fn to_json(&self) -> json::JsonValue {
+        json::object! {
+            "PlayerLPieceCoordinates": self.l_piece.map(|position| position.to_vec()).to_vec(),
+            "NeutralPieceCoordinates": self.neutral_pieces.map(|position| position.to_vec()).to_vec()
+        }
+    }
+
+    /// Reads a place-pieces command; `None` if it doesn't have four L-piece and two neutral piece coordinates.
+    pub fn from_json(board_move_json: &json::JsonValue) -> Option<BoardMove> {
+        fn positions<const N: usize>(positions_json: &json::JsonValue) -> Option<[[i32; 2]; N]> {
+            if !positions_json.is_array() || positions_json.len() != N {
+                return None;
+            }
+            let mut positions = [[0; 2]; N];
+            for (i, position) in positions.iter_mut().enumerate() {
+                if positions_json[i].len() != 2 {
+                    return None;
+                }
+                *position = [positions_json[i][0].as_i32()?, positions_json[i][1].as_i32()?];
+            }
+            Some(positions)
+        }
+
+        Some(BoardMove {
+            l_piece: positions(&board_move_json["PlayerLPieceCoordinates"])?,
+            neutral_pieces: positions(&board_move_json["NeutralPieceCoordinates"])?,
+        })
+    }
+
+    pub fn transformed(&self, symmetry: Symmetry) -> BoardMove {
+        BoardMove {
+            l_piece: self.l_piece.map(|position| symmetry.apply(position)),
+            neutral_pieces: self.neutral_pieces.map(|position| symmetry.apply(position)),
+        }
+    }
+
+    /// Whether the L-piece ends up on one of the corners, which scores a point.
+    pub fn occupies_corner(&self) -> bool {
+        self.l_piece.iter().any(|[x,y]| (*x == 0 || *x == 3) && (*y == 0 || *y == 3))
+    }
+}
+
+/// A move packed into 16 bits: the index of the L-piece placement in bits 8 to 13, and the squares the moved
+/// neutral piece comes from and goes to in bits 4 to 7 and 0 to 3. Both squares are 0 when no neutral piece moves.
+///
+/// Unlike `BoardMove`, the order of the squares doesn't matter, so equal moves have equal encodings. Only the
+/// position the move is played in knows where the neutral pieces are, so converting needs the `BoardState`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct CompactMove(u16);
+
+impl CompactMove {
+    /// `None` if `bits` is not a canonical encoding.
+    pub fn from_bits(bits: u16) -> Option<CompactMove> {
+        let compact_move = CompactMove(bits);
+        let (from, to) = (bits >> 4 & 0xf, bits & 0xf);
+        let canonical = bits >> 14 == 0 && compact_move.placement() < L_PIECE_MASKS.len() && (from != to || from == 0);
+        canonical.then_some(compact_move)
+    }
+
+    pub fn bits(self) -> u16 {
+        self.0
+    }
+
+    /// Index of the L-piece placement, in the order of `BoardState::l_piece_placements`.
+    pub fn placement(self) -> usize {
+        (self.0 >> 8) as usize
+    }
+
+    /// The squares (numbered row by row) the moved neutral piece comes from and goes to.
+    pub fn neutral_piece_move(self) -> Option<(u32, u32)> {
+        let (from, to) = ((self.0 >> 4 & 0xf) as u32, (self.0 & 0xf) as u32);
+        (from != to).then_some((from, to))
+    }
+
+    /// Encodes `board_move` as played in `board_state`; `None` if its L-piece is not an L on the board, or if it
+    /// doesn't put the neutral pieces on two squares with at most one of them moved.
+    pub fn from_board_move(board_state: &BoardState, board_move: &BoardMove) -> Option<CompactMove> {
+        let in_bounds = |positions: &[[i32; 2]]| positions.iter().all(|[x, y]| (0..4).contains(x) && (0..4).contains(y));
+        if !in_bounds(&board_move.l_piece) || !in_bounds(&board_move.neutral_pieces) {
+            return None;
+        }
+
+        let placement = L_PIECE_MASKS.iter().position(|mask| *mask == positions_mask(&board_move.l_piece))?;
+
+        let neutral_pieces = positions_mask(&board_move.neutral_pieces);
+        if neutral_pieces.count_ones() != 2 {
+            return None;
+        }
+
+        let (from, to) = match (board_state.neutral_pieces & !neutral_pieces, neutral_pieces & !board_state.neutral_pieces) {
+            (0, 0) => (0, 0),
+            (from, to) if from.count_ones() == 1 && to.count_ones() == 1 => (from.trailing_zeros(), to.trailing_zeros()),
+            _ => return None,
+        };
+
+        Some(CompactMove((placement as u16) << 8 | (from as u16) << 4 | to as u16))
+    }
+
+    /// The move in `board_state`, with the squares in the order `BoardState::legal_moves` generates them.
+    pub fn to_board_move(self, board_state: &BoardState) -> BoardMove {
+        let mut neutral_squares = squares(board_state.neutral_pieces);
+        let mut neutral_pieces = [neutral_squares.next().unwrap_or_default(), neutral_squares.next().unwrap_or_default()];
+
+        if let Some((from, to)) = self.neutral_piece_move() {
+            for square in neutral_pieces.iter_mut().filter(|square| **square == from) {
+                *square = to;
+            }
+        }
+
+        BoardMove { l_piece: L_PIECE_PLACEMENTS[self.placement()], neutral_pieces: neutral_pieces.map(square_position) }
+    }
+
+    /// The place-pieces command of the match protocol.
+    pub fn to_json(self, board_state: &BoardState) -> json::JsonValue {
+        self.to_board_move(board_state).to_json()
+    }
+
+    pub fn from_json(board_state: &BoardState, board_move_json: &json::JsonValue) -> Option<CompactMove> {
+        CompactMove::from_board_move(board_state, &BoardMove::from_json(board_move_json)?)
+    }
 }
 
 #[cfg(test)]
@@ -241,11 +752,11 @@ mod tests {
     fn move_is_applied_correctly_no_score() {
         let board_state = BoardState::new();
 
-        let board_move = BoardMove{lPiece: [[0,2], [1,2], [2,2], [2,3]], neutralPieces: [[0,0], [3,0]]};
+        let board_move = BoardMove{l_piece: [[0,2], [1,2], [2,2], [2,3]], neutral_pieces: [[0,0], [3,0]]};
 
         let new_state = board_state.apply_move(&board_move);
 
-        assert_eq!(new_state.board, [4, 0, 0, 4, 0, 2, 2, 2, 1, 1, 1, 2, 0, 0, 1, 0]);
+        assert_eq!(new_state.board(), [4, 0, 0, 4, 0, 2, 2, 2, 1, 1, 1, 2, 0, 0, 1, 0]);
         assert_eq!(new_state.current_player, 2);
         assert_eq!(new_state.scores, (0, 0));
     }
@@ -255,40 +766,33 @@ mod tests {
         let current_board_state = BoardState::new();
 
         // move player 1's l-piece downward
-        let board_move = BoardMove{lPiece: [[0,2], [0,3], [1,3], [2,3]], neutralPieces: [[0,0], [3,0]]};
+        let board_move = BoardMove{l_piece: [[0,2], [0,3], [1,3], [2,3]], neutral_pieces: [[0,0], [3,0]]};
         let current_board_state = current_board_state.apply_move(&board_move);
 
-        assert_eq!(current_board_state.board, [4, 0, 0, 4, 0, 2, 2, 2, 1, 0, 0, 2, 1, 1, 1, 0]);
+        assert_eq!(current_board_state.board(), [4, 0, 0, 4, 0, 2, 2, 2, 1, 0, 0, 2, 1, 1, 1, 0]);
         assert_eq!(current_board_state.current_player, 2);
         assert_eq!(current_board_state.scores, (1, 0));
 
         // move player 2's l-piece downward
-        let board_move = BoardMove{lPiece: [[1,2], [2,2], [3,2], [3,3]], neutralPieces: [[0,0], [3,0]]};
+        let board_move = BoardMove{l_piece: [[1,2], [2,2], [3,2], [3,3]], neutral_pieces: [[0,0], [3,0]]};
         let current_board_state = current_board_state.apply_move(&board_move);
 
-        assert_eq!(current_board_state.board, [4, 0, 0, 4, 0, 0, 0, 0, 1, 2, 2, 2, 1, 1, 1, 2]);
+        assert_eq!(current_board_state.board(), [4, 0, 0, 4, 0, 0, 0, 0, 1, 2, 2, 2, 1, 1, 1, 2]);
         assert_eq!(current_board_state.current_player, 1);
         assert_eq!(current_board_state.scores, (1, 1));
     }
 
     #[test]
     fn losing_state_has_no_more_moves() {
-        let current_board_state = BoardState { board: [0, 0, 4, 0,
-                                                       2, 2, 2, 0,
-                                                       1, 0, 2, 0,
-                                                       1, 1, 1, 4],
-                                               current_player: 1,
-                                               scores: (0,0) };
+        let current_board_state = BoardState::from_board(TRAPPING_BOARD, 1, (0, 0));
 
 
         assert_eq!(current_board_state.calculate_legal_moves(), vec![]);
 
-        let current_board_state = BoardState { board: [0, 0, 2, 0,
-                                                       0, 4, 2, 0,
-                                                       4, 1, 2, 2,
-                                                       0, 1, 1, 1],
-                                               current_player: 1,
-                                               scores: (0,0) };
+        let current_board_state = BoardState::from_board([0, 0, 2, 0,
+                                                          0, 4, 2, 0,
+                                                          4, 1, 2, 2,
+                                                          0, 1, 1, 1], 1, (0,0));
 
         assert_eq!(current_board_state.calculate_legal_moves(), vec![]);
     }
@@ -299,14 +803,18 @@ mod tests {
 
         assert_eq!(current_board_state.calculate_legal_moves().len(), 5 * 13);
 
-        assert_eq!(current_board_state.calculate_legal_moves(), vec![BoardMove { lPiece: [[0, 2], [0, 3], [1, 2], [2, 2]], neutralPieces: [[0, 0], [3, 3]] }, BoardMove { lPiece: [[0, 2], [0, 3], [1, 2], [2, 2]], neutralPieces: [[1, 0], [3, 3]] }, BoardMove { lPiece: [[0, 2], [0, 3], [1, 2], [2, 2]], neutralPieces: [[2, 0], [3, 3]] }, BoardMove { lPiece: [[0, 2], [0, 3], [1, 2], [2, 2]], neutralPieces: [[3, 0], [3, 3]] }, BoardMove { lPiece: [[0, 2], [0, 3], [1, 2], [2, 2]], neutralPieces: [[0, 1], [3, 3]] }, BoardMove { lPiece: [[0, 2], [0, 3], [1, 2], [2, 2]], neutralPieces: [[1, 3], [3, 3]] }, BoardMove { lPiece: [[0, 2], [0, 3], [1, 2], [2, 2]], neutralPieces: [[2, 3], [3, 3]] }, BoardMove { lPiece: [[0, 2], [0, 3], [1, 2], [2, 2]], neutralPieces: [[0, 0], [1, 0]] }, BoardMove { lPiece: [[0, 2], [0, 3], [1, 2], [2, 2]], neutralPieces: [[0, 0], [2, 0]] }, BoardMove { lPiece: [[0, 2], [0, 3], [1, 2], [2, 2]], neutralPieces: [[0, 0], [3, 0]] }, BoardMove { lPiece: [[0, 2], [0, 3], [1, 2], [2, 2]], neutralPieces: [[0, 0], [0, 1]] }, BoardMove { lPiece: [[0, 2], [0, 3], [1, 2], [2, 2]], neutralPieces: [[0, 0], [1, 3]] }, BoardMove { lPiece: [[0, 2], [0, 3], [1, 2], [2, 2]], neutralPieces: [[0, 0], [2, 3]] }, BoardMove { lPiece: [[2, 2], [2, 3], [1, 2], [0, 2]], neutralPieces: [[0, 0], [3, 3]] }, BoardMove { lPiece: [[2, 2], [2, 3], [1, 2], [0, 2]], neutralPieces: [[1, 0], [3, 3]] }, BoardMove { lPiece: [[2, 2], [2, 3], [1, 2], [0, 2]], neutralPieces: [[2, 0], [3, 3]] }, BoardMove { lPiece: [[2, 2], [2, 3], [1, 2], [0, 2]], neutralPieces: [[3, 0], [3, 3]] }, BoardMove { lPiece: [[2, 2], [2, 3], [1, 2], [0, 2]], neutralPieces: [[0, 1], [3, 3]] }, BoardMove { lPiece: [[2, 2], [2, 3], [1, 2], [0, 2]], neutralPieces: [[0, 3], [3, 3]] }, BoardMove { lPiece: [[2, 2], [2, 3], [1, 2], [0, 2]], neutralPieces: [[1, 3], [3, 3]] }, BoardMove { lPiece: [[2, 2], [2, 3], [1, 2], [0, 2]], neutralPieces: [[0, 0], [1, 0]] }, BoardMove { lPiece: [[2, 2], [2, 3], [1, 2], [0, 2]], neutralPieces: [[0, 0], [2, 0]] }, BoardMove { lPiece: [[2, 2], [2, 3], [1, 2], [0, 2]], neutralPieces: [[0, 0], [3, 0]] }, BoardMove { lPiece: [[2, 2], [2, 3], [1, 2], [0, 2]], neutralPieces: [[0, 0], [0, 1]] }, BoardMove { lPiece: [[2, 2], [2, 3], [1, 2], [0, 2]], neutralPieces: [[0, 0], [0, 3]] }, BoardMove { lPiece: [[2, 2], [2, 3], [1, 2], [0, 2]], neutralPieces: [[0, 0], [1, 3]] }, BoardMove { lPiece: [[0, 3], [1, 3], [0, 2], [0, 1]], neutralPieces: [[0, 0], [3, 3]] }, BoardMove { lPiece: [[0, 3], [1, 3], [0, 2], [0, 1]], neutralPieces: [[1, 0], [3, 3]] }, BoardMove { lPiece: [[0, 3], [1, 3], [0, 2], [0, 1]], neutralPieces: [[2, 0], [3, 3]] }, BoardMove { lPiece: [[0, 3], [1, 3], [0, 2], [0, 1]], neutralPieces: [[3, 0], [3, 3]] }, BoardMove { lPiece: [[0, 3], [1, 3], [0, 2], [0, 1]], neutralPieces: [[1, 2], [3, 3]] }, BoardMove { lPiece: [[0, 3], [1, 3], [0, 2], [0, 1]], neutralPieces: [[2, 2], [3, 3]] }, BoardMove { lPiece: [[0, 3], [1, 3], [0, 2], [0, 1]], neutralPieces: [[2, 3], [3, 3]] }, BoardMove { lPiece: [[0, 3], [1, 3], [0, 2], [0, 1]], neutralPieces: [[0, 0], [1, 0]] }, BoardMove { lPiece: [[0, 3], [1, 3], [0, 2], [0, 1]], neutralPieces: [[0, 0], [2, 0]] }, BoardMove { lPiece: [[0, 3], [1, 3], [0, 2], [0, 1]], neutralPieces: [[0, 0], [3, 0]] }, BoardMove { lPiece: [[0, 3], [1, 3], [0, 2], [0, 1]], neutralPieces: [[0, 0], [1, 2]] }, BoardMove { lPiece: [[0, 3], [1, 3], [0, 2], [0, 1]], neutralPieces: [[0, 0], [2, 2]] }, BoardMove { lPiece: [[0, 3], [1, 3], [0, 2], [0, 1]], neutralPieces: [[0, 0], [2, 3]] }, BoardMove { lPiece: [[0, 3], [0, 2], [1, 3], [2, 3]], neutralPieces: [[0, 0], [3, 3]] }, BoardMove { lPiece: [[0, 3], [0, 2], [1, 3], [2, 3]], neutralPieces: [[1, 0], [3, 3]] }, BoardMove { lPiece: [[0, 3], [0, 2], [1, 3], [2, 3]], neutralPieces: [[2, 0], [3, 3]] }, BoardMove { lPiece: [[0, 3], [0, 2], [1, 3], [2, 3]], neutralPieces: [[3, 0], [3, 3]] }, BoardMove { lPiece: [[0, 3], [0, 2], [1, 3], [2, 3]], neutralPieces: [[0, 1], [3, 3]] }, BoardMove { lPiece: [[0, 3], [0, 2], [1, 3], [2, 3]], neutralPieces: [[1, 2], [3, 3]] }, BoardMove { lPiece: [[0, 3], [0, 2], [1, 3], [2, 3]], neutralPieces: [[2, 2], [3, 3]] }, BoardMove { lPiece: [[0, 3], [0, 2], [1, 3], [2, 3]], neutralPieces: [[0, 0], [1, 0]] }, BoardMove { lPiece: [[0, 3], [0, 2], [1, 3], [2, 3]], neutralPieces: [[0, 0], [2, 0]] }, BoardMove { lPiece: [[0, 3], [0, 2], [1, 3], [2, 3]], neutralPieces: [[0, 0], [3, 0]] }, BoardMove { lPiece: [[0, 3], [0, 2], [1, 3], [2, 3]], neutralPieces: [[0, 0], [0, 1]] }, BoardMove { lPiece: [[0, 3], [0, 2], [1, 3], [2, 3]], neutralPieces: [[0, 0], [1, 2]] }, BoardMove { lPiece: [[0, 3], [0, 2], [1, 3], [2, 3]], neutralPieces: [[0, 0], [2, 2]] }, BoardMove { lPiece: [[2, 3], [2, 2], [1, 3], [0, 3]], neutralPieces: [[0, 0], [3, 3]] }, BoardMove { lPiece: [[2, 3], [2, 2], [1, 3], [0, 3]], neutralPieces: [[1, 0], [3, 3]] }, BoardMove { lPiece: [[2, 3], [2, 2], [1, 3], [0, 3]], neutralPieces: [[2, 0], [3, 3]] }, BoardMove { lPiece: [[2, 3], [2, 2], [1, 3], [0, 3]], neutralPieces: [[3, 0], [3, 3]] }, BoardMove { lPiece: [[2, 3], [2, 2], [1, 3], [0, 3]], neutralPieces: [[0, 1], [3, 3]] }, BoardMove { lPiece: [[2, 3], [2, 2], [1, 3], [0, 3]], neutralPieces: [[0, 2], [3, 3]] }, BoardMove { lPiece: [[2, 3], [2, 2], [1, 3], [0, 3]], neutralPieces: [[1, 2], [3, 3]] }, BoardMove { lPiece: [[2, 3], [2, 2], [1, 3], [0, 3]], neutralPieces: [[0, 0], [1, 0]] }, BoardMove { lPiece: [[2, 3], [2, 2], [1, 3], [0, 3]], neutralPieces: [[0, 0], [2, 0]] }, BoardMove { lPiece: [[2, 3], [2, 2], [1, 3], [0, 3]], neutralPieces: [[0, 0], [3, 0]] }, BoardMove { lPiece: [[2, 3], [2, 2], [1, 3], [0, 3]], neutralPieces: [[0, 0], [0, 1]] }, BoardMove { lPiece: [[2, 3], [2, 2], [1, 3], [0, 3]], neutralPieces: [[0, 0], [0, 2]] }, BoardMove { lPiece: [[2, 3], [2, 2], [1, 3], [0, 3]], neutralPieces: [[0, 0], [1, 2]] }]);
+        assert_eq!(current_board_state.calculate_legal_moves(), vec![BoardMove { l_piece: [[0, 2], [0, 3], [1, 2], [2, 2]], neutral_pieces: [[0, 0], [3, 3]] }, BoardMove { l_piece: [[0, 2], [0, 3], [1, 2], [2, 2]], neutral_pieces: [[1, 0], [3, 3]] }, BoardMove { l_piece: [[0, 2], [0, 3], [1, 2], [2, 2]], neutral_pieces: [[2, 0], [3, 3]] }, BoardMove { l_piece: [[0, 2], [0, 3], [1, 2], [2, 2]], neutral_pieces: [[3, 0], [3, 3]] }, BoardMove { l_piece: [[0, 2], [0, 3], [1, 2], [2, 2]], neutral_pieces: [[0, 1], [3, 3]] }, BoardMove { l_piece: [[0, 2], [0, 3], [1, 2], [2, 2]], neutral_pieces: [[1, 3], [3, 3]] }, BoardMove { l_piece: [[0, 2], [0, 3], [1, 2], [2, 2]], neutral_pieces: [[2, 3], [3, 3]] }, BoardMove { l_piece: [[0, 2], [0, 3], [1, 2], [2, 2]], neutral_pieces: [[0, 0], [1, 0]] }, BoardMove { l_piece: [[0, 2], [0, 3], [1, 2], [2, 2]], neutral_pieces: [[0, 0], [2, 0]] }, BoardMove { l_piece: [[0, 2], [0, 3], [1, 2], [2, 2]], neutral_pieces: [[0, 0], [3, 0]] }, BoardMove { l_piece: [[0, 2], [0, 3], [1, 2], [2, 2]], neutral_pieces: [[0, 0], [0, 1]] }, BoardMove { l_piece: [[0, 2], [0, 3], [1, 2], [2, 2]], neutral_pieces: [[0, 0], [1, 3]] }, BoardMove { l_piece: [[0, 2], [0, 3], [1, 2], [2, 2]], neutral_pieces: [[0, 0], [2, 3]] }, BoardMove { l_piece: [[2, 2], [2, 3], [1, 2], [0, 2]], neutral_pieces: [[0, 0], [3, 3]] }, BoardMove { l_piece: [[2, 2], [2, 3], [1, 2], [0, 2]], neutral_pieces: [[1, 0], [3, 3]] }, BoardMove { l_piece: [[2, 2], [2, 3], [1, 2], [0, 2]], neutral_pieces: [[2, 0], [3, 3]] }, BoardMove { l_piece: [[2, 2], [2, 3], [1, 2], [0, 2]], neutral_pieces: [[3, 0], [3, 3]] }, BoardMove { l_piece: [[2, 2], [2, 3], [1, 2], [0, 2]], neutral_pieces: [[0, 1], [3, 3]] }, BoardMove { l_piece: [[2, 2], [2, 3], [1, 2], [0, 2]], neutral_pieces: [[0, 3], [3, 3]] }, BoardMove { l_piece: [[2, 2], [2, 3], [1, 2], [0, 2]], neutral_pieces: [[1, 3], [3, 3]] }, BoardMove { l_piece: [[2, 2], [2, 3], [1, 2], [0, 2]], neutral_pieces: [[0, 0], [1, 0]] }, BoardMove { l_piece: [[2, 2], [2, 3], [1, 2], [0, 2]], neutral_pieces: [[0, 0], [2, 0]] }, BoardMove { l_piece: [[2, 2], [2, 3], [1, 2], [0, 2]], neutral_pieces: [[0, 0], [3, 0]] }, BoardMove { l_piece: [[2, 2], [2, 3], [1, 2], [0, 2]], neutral_pieces: [[0, 0], [0, 1]] }, BoardMove { l_piece: [[2, 2], [2, 3], [1, 2], [0, 2]], neutral_pieces: [[0, 0], [0, 3]] }, BoardMove { l_piece: [[2, 2], [2, 3], [1, 2], [0, 2]], neutral_pieces: [[0, 0], [1, 3]] }, BoardMove { l_piece: [[0, 3], [1, 3], [0, 2], [0, 1]], neutral_pieces: [[0, 0], [3, 3]] }, BoardMove { l_piece: [[0, 3], [1, 3], [0, 2], [0, 1]], neutral_pieces: [[1, 0], [3, 3]] }, BoardMove { l_piece: [[0, 3], [1, 3], [0, 2], [0, 1]], neutral_pieces: [[2, 0], [3, 3]] }, BoardMove { l_piece: [[0, 3], [1, 3], [0, 2], [0, 1]], neutral_pieces: [[3, 0], [3, 3]] }, BoardMove { l_piece: [[0, 3], [1, 3], [0, 2], [0, 1]], neutral_pieces: [[1, 2], [3, 3]] }, BoardMove { l_piece: [[0, 3], [1, 3], [0, 2], [0, 1]], neutral_pieces: [[2, 2], [3, 3]] }, BoardMove { l_piece: [[0, 3], [1, 3], [0, 2], [0, 1]], neutral_pieces: [[2, 3], [3, 3]] }, BoardMove { l_piece: [[0, 3], [1, 3], [0, 2], [0, 1]], neutral_pieces: [[0, 0], [1, 0]] }, BoardMove { l_piece: [[0, 3], [1, 3], [0, 2], [0, 1]], neutral_pieces: [[0, 0], [2, 0]] }, BoardMove { l_piece: [[0, 3], [1, 3], [0, 2], [0, 1]], neutral_pieces: [[0, 0], [3, 0]] }, BoardMove { l_piece: [[0, 3], [1, 3], [0, 2], [0, 1]], neutral_pieces: [[0, 0], [1, 2]] }, BoardMove { l_piece: [[0, 3], [1, 3], [0, 2], [0, 1]], neutral_pieces: [[0, 0], [2, 2]] }, BoardMove { l_piece: [[0, 3], [1, 3], [0, 2], [0, 1]], neutral_pieces: [[0, 0], [2, 3]] }, BoardMove { l_piece: [[0, 3], [0, 2], [1, 3], [2, 3]], neutral_pieces: [[0, 0], [3, 3]] }, BoardMove { l_piece: [[0, 3], [0, 2], [1, 3], [2, 3]], neutral_pieces: [[1, 0], [3, 3]] }, BoardMove { l_piece: [[0, 3], [0, 2], [1, 3], [2, 3]], neutral_pieces: [[2, 0], [3, 3]] }, BoardMove { l_piece: [[0, 3], [0, 2], [1, 3], [2, 3]], neutral_pieces: [[3, 0], [3, 3]] }, BoardMove { l_piece: [[0, 3], [0, 2], [1, 3], [2, 3]], neutral_pieces: [[0, 1], [3, 3]] }, BoardMove { l_piece: [[0, 3], [0, 2], [1, 3], [2, 3]], neutral_pieces: [[1, 2], [3, 3]] }, BoardMove { l_piece: [[0, 3], [0, 2], [1, 3], [2, 3]], neutral_pieces: [[2, 2], [3, 3]] }, BoardMove { l_piece: [[0, 3], [0, 2], [1, 3], [2, 3]], neutral_pieces: [[0, 0], [1, 0]] }, BoardMove { l_piece: [[0, 3], [0, 2], [1, 3], [2, 3]], neutral_pieces: [[0, 0], [2, 0]] }, BoardMove { l_piece: [[0, 3], [0, 2], [1, 3], [2, 3]], neutral_pieces: [[0, 0], [3, 0]] }, BoardMove { l_piece: [[0, 3], [0, 2], [1, 3], [2, 3]], neutral_pieces: [[0, 0], [0, 1]] }, BoardMove { l_piece: [[0, 3], [0, 2], [1, 3], [2, 3]], neutral_pieces: [[0, 0], [1, 2]] }, BoardMove { l_piece: [[0, 3], [0, 2], [1, 3], [2, 3]], neutral_pieces: [[0, 0], [2, 2]] }, BoardMove { l_piece: [[2, 3], [2, 2], [1, 3], [0, 3]], neutral_pieces: [[0, 0], [3, 3]] }, BoardMove { l_piece: [[2, 3], [2, 2], [1, 3], [0, 3]], neutral_pieces: [[1, 0], [3, 3]] }, BoardMove { l_piece: [[2, 3], [2, 2], [1, 3], [0, 3]], neutral_pieces: [[2, 0], [3, 3]] }, BoardMove { l_piece: [[2, 3], [2, 2], [1, 3], [0, 3]], neutral_pieces: [[3, 0], [3, 3]] }, BoardMove { l_piece: [[2, 3], [2, 2], [1, 3], [0, 3]], neutral_pieces: [[0, 1], [3, 3]] }, BoardMove { l_piece: [[2, 3], [2, 2], [1, 3], [0, 3]], neutral_pieces: [[0, 2], [3, 3]] }, BoardMove { l_piece: [[2, 3], [2, 2], [1, 3], [0, 3]], neutral_pieces: [[1, 2], [3, 3]] }, BoardMove { l_piece: [[2, 3], [2, 2], [1, 3], [0, 3]], neutral_pieces: [[0, 0], [1, 0]] }, BoardMove { l_piece: [[2, 3], [2, 2], [1, 3], [0, 3]], neutral_pieces: [[0, 0], [2, 0]] }, BoardMove { l_piece: [[2, 3], [2, 2], [1, 3], [0, 3]], neutral_pieces: [[0, 0], [3, 0]] }, BoardMove { l_piece: [[2, 3], [2, 2], [1, 3], [0, 3]], neutral_pieces: [[0, 0], [0, 1]] }, BoardMove { l_piece: [[2, 3], [2, 2], [1, 3], [0, 3]], neutral_pieces: [[0, 0], [0, 2]] }, BoardMove { l_piece: [[2, 3], [2, 2], [1, 3], [0, 3]], neutral_pieces: [[0, 0], [1, 2]] }]);
     }
 
     #[test]
     fn begin_state_get_optimal_move() {
         let current_board_state = BoardState::new();
 
-        assert_eq!(current_board_state.calculate_optimal_move(0), Some(BoardMove { lPiece: [[2, 3], [2, 2], [1, 3], [0, 3]], neutralPieces: [[0, 0], [1, 2]] }));
+        let chosen_move = current_board_state.calculate_optimal_move(0).unwrap();
+
+        // of the moves that score, the search keeps the first it generates
+        assert_eq!(chosen_move, BoardMove { l_piece: [[0, 2], [0, 3], [1, 2], [2, 2]], neutral_pieces: [[0, 0], [3, 3]] });
+        assert_eq!(current_board_state.apply_move(&chosen_move).scores(), (1, 0));
     }
 
     #[test]
@@ -329,9 +837,10 @@ mod tests {
           }
           "#).unwrap();
 
-        let board_state = BoardState::load(json_value);
+        let board_state = BoardState::load(json_value).unwrap();
 
-        assert_eq!(board_state, BoardState::new());
+        assert_eq!(board_state, BoardState::new().with_turn(2));
+        assert_eq!(board_state.turn(), 2);
         assert_eq!(board_state.current_player(), 1);
         assert_eq!(board_state.other_player(), 2);
     }
@@ -356,25 +865,384 @@ mod tests {
           }
           "#).unwrap();
 
-        let board_state = BoardState::load(json_value);
+        let board_state = BoardState::load(json_value).unwrap();
 
         assert_eq!(board_state.current_player(), 2);
         assert_eq!(board_state.other_player(), 1);
     }
 
+    #[test]
+    fn load_reports_invalid_game_states() {
+        let load = |board: &str, player: &str| BoardState::load(json::parse(&format!(r#"{{
+            "GameState": {{ "Board": {{ "Board": {} }}, "ScorePlayer0": 0, "ScorePlayer1": 0 }},
+            "Turn": 2,
+            "Player": {}
+          }}"#, board, player)).unwrap());
+
+        assert_eq!(load("[[4, 0, 0, 0], [1, 2, 2, 2], [1, 1, 1, 2], [0, 0, 0, 4]]", "0"), Ok(BoardState::new().with_turn(2)));
+        assert_eq!(load("[[4, 0, 0, 0], [1, 2, 2, 2], [1, 1, 1, 2], [0, 0, 0, 4]]", "2"), Err(LoadError::InvalidField("Player")));
+        assert_eq!(load("[[4, 0, 0, 0], [1, 2, 2, 2], [1, 1, 1, 2], [0, 0, 0, 4]]", r#""0""#), Err(LoadError::InvalidField("Player")));
+        assert_eq!(load("null", "0"), Err(LoadError::InvalidField("GameState.Board.Board")));
+        assert_eq!(load("[[4, 0, 0, 0], [1, 2, 2, 2], [1, 1, 1, 2]]", "0"), Err(LoadError::BoardSize));
+        assert_eq!(load("[[4, 0, 0, 0], [1, 2, 2, 2], [1, 1, 1, 2], [0, 0, 4]]", "0"), Err(LoadError::BoardSize));
+        assert_eq!(load("[[4, 0, 0, 0], [1, 2, 2, 2], [1, 1, 1, 2], [0, 0, 3, 4]]", "0"), Err(LoadError::InvalidSquare { x: 2, y: 3 }));
+        assert_eq!(load("[[4, 0, 0, 0], [1, 2, 2, 2], [1, 1, 1, 2], [0, 0, 0, 0]]", "0"), Err(LoadError::PieceCount { piece: 4, count: 1 }));
+        assert_eq!(load("[[4, 0, 0, 0], [1, 2, 2, 2], [1, 1, 1, 2], [0, 0, 1, 4]]", "0"), Err(LoadError::PieceCount { piece: 1, count: 5 }));
+        assert_eq!(load("[[4, 0, 0, 0], [1, 2, 2, 2], [1, 1, 0, 2], [0, 0, 1, 4]]", "0"), Err(LoadError::NotAnLShape { player: 1 }));
+        assert_eq!(BoardState::load(json::parse(r#"{"Player": 0}"#).unwrap()), Err(LoadError::InvalidField("GameState.ScorePlayer0")));
+
+        let load_turn = |turn: &str| BoardState::load(json::parse(&format!(r#"{{
+            "GameState": {{ "Board": {{ "Board": [[4, 0, 0, 0], [1, 2, 2, 2], [1, 1, 1, 2], [0, 0, 0, 4]] }}, "ScorePlayer0": 0, "ScorePlayer1": 0 }},
+            {}
+            "Player": 0
+          }}"#, turn)).unwrap());
+
+        assert_eq!(load_turn(""), Ok(BoardState::new().with_turn(1)));
+        assert_eq!(load_turn(r#""Turn": -1,"#), Err(LoadError::InvalidField("Turn")));
+    }
+
+    #[test]
+    fn board_state_json_round_trips() {
+        let board_state = BoardState::from_board(TRAPPING_BOARD, 2, (3, 5)).with_turn(12);
+
+        let json_value = board_state.to_json();
+
+        assert_eq!(json_value["Turn"], 12);
+        assert_eq!(BoardState::load(json::parse(&json_value.dump()).unwrap()), Ok(board_state));
+    }
+
+    #[test]
+    fn board_move_json_round_trips() {
+        let board_move = BoardMove { l_piece: [[1, 0], [1, 1], [2, 0], [3, 0]], neutral_pieces: [[0, 0], [3, 3]] };
+
+        assert_eq!(board_move.to_json().dump(), r#"{"PlayerLPieceCoordinates":[[1,0],[1,1],[2,0],[3,0]],"NeutralPieceCoordinates":[[0,0],[3,3]]}"#);
+        assert_eq!(BoardMove::from_json(&board_move.to_json()), Some(board_move));
+        assert_eq!(BoardMove::from_json(&json::parse(r#"{"PlayerLPieceCoordinates":[[1,0],[1,1],[2,0]],"NeutralPieceCoordinates":[[0,0],[3,3]]}"#).unwrap()), None);
+        assert_eq!(BoardMove::from_json(&json::parse(r#"{"PlayerLPieceCoordinates":[[1,0],[1,1],[2,0],[3,"0"]],"NeutralPieceCoordinates":[[0,0],[3,3]]}"#).unwrap()), None);
+        assert_eq!(BoardMove::from_json(&json::parse(r#"{"NeutralPieceCoordinates":[[0,0],[3,3]]}"#).unwrap()), None);
+    }
+
+    #[test]
+    fn legal_moves_are_recognized() {
+        let board_state = BoardState::new();
+
+        assert!(board_state.is_legal_move(&BoardMove { l_piece: [[0, 2], [0, 3], [1, 2], [2, 2]], neutral_pieces: [[0, 0], [3, 3]] }));
+        // same squares in a different order
+        assert!(board_state.is_legal_move(&BoardMove { l_piece: [[2, 2], [1, 2], [0, 3], [0, 2]], neutral_pieces: [[3, 3], [0, 0]] }));
+        // L-piece didn't move
+        assert!(!board_state.is_legal_move(&BoardMove { l_piece: [[0, 1], [0, 2], [1, 2], [2, 2]], neutral_pieces: [[0, 0], [3, 3]] }));
+        // not an L
+        assert!(!board_state.is_legal_move(&BoardMove { l_piece: [[0, 2], [0, 3], [1, 3], [2, 2]], neutral_pieces: [[0, 0], [3, 3]] }));
+        // off the board
+        assert!(!board_state.is_legal_move(&BoardMove { l_piece: [[0, 2], [0, 3], [1, 2], [2, 2]], neutral_pieces: [[0, 0], [4, 3]] }));
+    }
+
+    #[test]
+    fn illegal_moves_are_explained() {
+        let board_state = BoardState::new();
+        let validate = |l_piece, neutral_pieces| board_state.validate_move(&BoardMove { l_piece, neutral_pieces });
+
+        assert_eq!(validate([[0, 2], [0, 3], [1, 2], [2, 2]], [[0, 0], [3, 3]]), Ok(()));
+        assert_eq!(validate([[0, 2], [0, 3], [1, 2], [2, 2]], [[0, 0], [4, 3]]), Err(IllegalMove::OutOfBounds([4, 3])));
+        assert_eq!(validate([[0, 2], [0, 3], [1, 3], [2, 2]], [[0, 0], [3, 3]]), Err(IllegalMove::NotAnLShape));
+        assert_eq!(validate([[0, 2], [0, 2], [1, 2], [2, 2]], [[0, 0], [3, 3]]), Err(IllegalMove::NotAnLShape));
+        assert_eq!(validate([[0, 1], [0, 2], [0, 3], [1, 1]], [[0, 0], [3, 3]]), Err(IllegalMove::LPieceOnOpponent));
+        assert_eq!(validate([[0, 0], [0, 1], [0, 2], [1, 2]], [[0, 0], [3, 3]]), Err(IllegalMove::LPieceOnNeutralPiece));
+        assert_eq!(validate([[0, 1], [0, 2], [1, 2], [2, 2]], [[0, 0], [3, 3]]), Err(IllegalMove::LPieceNotMoved));
+        assert_eq!(validate([[0, 2], [0, 3], [1, 2], [2, 2]], [[0, 0], [0, 0]]), Err(IllegalMove::NeutralPiecesOnSameSquare));
+        assert_eq!(validate([[0, 2], [0, 3], [1, 2], [2, 2]], [[0, 0], [1, 1]]), Err(IllegalMove::NeutralPieceOnLPiece([1, 1])));
+        assert_eq!(validate([[0, 2], [0, 3], [1, 2], [2, 2]], [[0, 3], [3, 3]]), Err(IllegalMove::NeutralPieceOnLPiece([0, 3])));
+        assert_eq!(validate([[0, 2], [0, 3], [1, 2], [2, 2]], [[1, 0], [2, 3]]), Err(IllegalMove::BothNeutralPiecesMoved));
+
+        // a neutral piece may move to the square the L-piece left
+        assert_eq!(validate([[0, 2], [0, 3], [1, 2], [2, 2]], [[0, 1], [3, 3]]), Ok(()));
+    }
+
+    #[test]
+    fn validation_agrees_with_move_generation() {
+        let mut rng = crate::rng::Rng::new(5);
+        let mut board_state = BoardState::new();
+
+        for _ in 0..30 {
+            let legal_moves = board_state.calculate_legal_moves();
+
+            for l_piece in BoardState::l_piece_placements() {
+                for neutral_pieces in [[[0, 0], [3, 3]], [[1, 1], [2, 2]], [[0, 3], [3, 0]]] {
+                    let board_move = BoardMove { l_piece, neutral_pieces };
+                    let new_state = board_state.apply_move(&board_move);
+                    let generated = legal_moves.iter().any(|legal_move| board_state.apply_move(legal_move) == new_state);
+
+                    assert_eq!(board_state.validate_move(&board_move).is_ok(), generated, "{:?} in {:?}", board_move, board_state);
+                }
+            }
+            for board_move in &legal_moves {
+                assert_eq!(board_state.validate_move(board_move), Ok(()));
+            }
+
+            match rng.choose(&legal_moves) {
+                Some(board_move) => board_state = board_state.apply_move(board_move),
+                None => board_state = BoardState::new(),
+            }
+        }
+    }
+
     #[test]
     fn erroneous_move() {
 
         let board_state = BoardState::new();
 
-        let board_state = board_state.apply_move(&BoardMove{ lPiece: [[0,2],[1,2],[2,2],[0,3]], neutralPieces: [[0,0],[3,3]]});
+        let board_state = board_state.apply_move(&BoardMove{ l_piece: [[0,2],[1,2],[2,2],[0,3]], neutral_pieces: [[0,0],[3,3]]});
 
-        assert_eq!(board_state, BoardState { board: [4, 0, 0, 0, 0, 2, 2, 2, 1, 1, 1, 2, 1, 0, 0, 4], current_player: 2, scores: (1, 0) });
+        assert_eq!(board_state, BoardState::from_board([4, 0, 0, 0, 0, 2, 2, 2, 1, 1, 1, 2, 1, 0, 0, 4], 2, (1, 0)).with_turn(2));
 
         let chosen_move = board_state.calculate_optimal_move(0).unwrap();
 
-        assert_ne!(chosen_move, BoardMove{ lPiece: [[2,2],[0,3],[1,3],[2,3]], neutralPieces: [[0,0],[1,2]]});
+        assert_ne!(chosen_move, BoardMove{ l_piece: [[2,2],[0,3],[1,3],[2,3]], neutral_pieces: [[0,0],[1,2]]});
+
+        assert_eq!(chosen_move, BoardMove { l_piece: [[1, 0], [1, 1], [2, 0], [3, 0]], neutral_pieces: [[0, 0], [3, 2]] });
+        assert_eq!(board_state.apply_move(&chosen_move).scores(), (1, 1));
+    }
+
+    #[test]
+    fn zobrist_hash_distinguishes_states() {
+        let board_state = BoardState::new();
+        let board_move = BoardMove{ l_piece: [[0,2],[1,2],[2,2],[0,3]], neutral_pieces: [[0,0],[3,3]]};
+        let next_board_state = board_state.apply_move(&board_move);
+
+        assert_eq!(board_state.zobrist_hash(), BoardState::new().zobrist_hash());
+        assert_ne!(board_state.zobrist_hash(), next_board_state.zobrist_hash());
+        assert_ne!(board_state.zobrist_hash(), BoardState { current_player: 2, ..BoardState::new() }.zobrist_hash());
+        assert_ne!(board_state.zobrist_hash(), BoardState { scores: (0, 1), ..BoardState::new() }.zobrist_hash());
+        assert_ne!(BoardState { scores: (1, 0), ..BoardState::new() }.zobrist_hash(), BoardState { scores: (0, 1), ..BoardState::new() }.zobrist_hash());
+    }
+
+    #[test]
+    fn symmetric_positions_share_canonical_form() {
+        let board_state = BoardState::new().apply_move(&BoardMove{ l_piece: [[0,2],[1,2],[2,2],[0,3]], neutral_pieces: [[0,0],[3,3]]});
+        let (canonical, symmetry) = board_state.canonical();
 
-        assert_eq!(chosen_move, BoardMove { lPiece: [[3, 1], [3, 0], [2, 1], [1, 1]], neutralPieces: [[0, 0], [2, 3]] });
+        assert_eq!(board_state.transformed(symmetry), canonical);
+        assert_eq!(canonical.canonical(), (canonical.clone(), Symmetry::IDENTITY));
+
+        for other_symmetry in Symmetry::ALL {
+            assert_eq!(board_state.transformed(other_symmetry).canonical().0, canonical);
+        }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn moves_map_through_symmetry() {
+        let board_state = BoardState::new().apply_move(&BoardMove{ l_piece: [[0,2],[1,2],[2,2],[0,3]], neutral_pieces: [[0,0],[3,3]]});
+
+        for symmetry in Symmetry::ALL {
+            let transformed_state = board_state.transformed(symmetry);
+            let transformed_moves = transformed_state.calculate_legal_moves();
+
+            assert_eq!(transformed_moves.len(), board_state.calculate_legal_moves().len());
+
+            for board_move in transformed_moves {
+                let original_move = board_move.transformed(symmetry.inverse());
+
+                // scoring is symmetric too, so the results only differ by the symmetry
+                assert_eq!(board_state.apply_move(&original_move).transformed(symmetry), transformed_state.apply_move(&board_move));
+            }
+        }
+    }
+
+    #[test]
+    fn l_piece_has_48_placements() {
+        let placements = BoardState::l_piece_placements();
+
+        assert_eq!(placements.len(), 48);
+        assert!(placements.contains(&[[0, 1], [0, 2], [1, 1], [2, 1]]));
+    }
+
+    #[test]
+    fn bitboards_match_board() {
+        let board = TRAPPING_BOARD;
+        let board_state = BoardState::from_board(board, 2, (3, 5));
+
+        assert_eq!(board_state.board(), board);
+        assert_eq!(board_state.l_pieces(), [0b0111_0001_0000_0000, 0b0000_0100_0111_0000]);
+        assert_eq!(board_state.neutral_pieces(), 0b1000_0000_0000_0100);
+        assert_eq!(BoardState::from_bitboards(board_state.l_pieces(), board_state.neutral_pieces(), 2, (3, 5)), board_state);
+
+        for (placement, mask) in L_PIECE_PLACEMENTS.iter().zip(L_PIECE_MASKS) {
+            assert_eq!(mask.count_ones(), 4);
+            assert_eq!(positions_mask(placement), mask);
+        }
+    }
+
+    /// Successor boards of the position by trying every placement of the L-piece and of the neutral pieces.
+    fn brute_force_successors(board_state: &BoardState) -> Vec<[u8; 16]> {
+        let board = board_state.board();
+        let player = board_state.current_player();
+        let mut successors = vec![];
+
+        for l_piece in BoardState::l_piece_placements() {
+            let l_piece_squares = l_piece.map(|[x, y]| (y * 4 + x) as usize);
+            if l_piece_squares.iter().any(|square| board[*square] != 0 && board[*square] != player) || l_piece_squares.iter().all(|square| board[*square] == player) {
+                continue;
+            }
+
+            let mut moved_board = board.map(|value| if value == player { 0 } else { value });
+            for square in l_piece_squares {
+                moved_board[square] = player;
+            }
+
+            for first in 0..16 {
+                for second in first + 1..16 {
+                    let free = |square: usize| moved_board[square] == 0 || moved_board[square] == 4;
+                    // at most one neutral piece moves
+                    if !free(first) || !free(second) || (board[first] != 4 && board[second] != 4) {
+                        continue;
+                    }
+
+                    let board_move = BoardMove { l_piece, neutral_pieces: [square_position(first as u32), square_position(second as u32)] };
+                    successors.push(board_state.apply_move(&board_move).board());
+                }
+            }
+        }
+
+        successors.sort();
+        successors.dedup();
+        successors
+    }
+
+    #[test]
+    fn legal_moves_are_distinct_and_complete() {
+        let mut rng = crate::rng::Rng::new(7);
+        let mut positions = 0;
+
+        while positions < 200 {
+            let mut board_state = BoardState::new();
+
+            for _ in 0..40 {
+                let legal_moves: Vec<BoardMove> = board_state.legal_moves().collect();
+                let mut successors: Vec<[u8; 16]> = legal_moves.iter().map(|board_move| board_state.apply_move(board_move).board()).collect();
+                successors.sort();
+
+                let count = successors.len();
+                successors.dedup();
+
+                assert_eq!(successors.len(), count, "duplicate successors of {:?}", board_state);
+                assert_eq!(successors, brute_force_successors(&board_state), "successors of {:?}", board_state);
+                positions += 1;
+
+                match rng.choose(&legal_moves) {
+                    Some(board_move) => board_state = board_state.apply_move(board_move),
+                    None => break,
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn compact_moves_round_trip() {
+        let mut rng = crate::rng::Rng::new(3);
+        let mut board_state = BoardState::new();
+
+        for _ in 0..30 {
+            let legal_moves = board_state.calculate_legal_moves();
+            let mut compact_moves = vec![];
+
+            for board_move in &legal_moves {
+                let compact_move = CompactMove::from_board_move(&board_state, board_move).unwrap();
+
+                assert_eq!(compact_move.to_board_move(&board_state), *board_move);
+                assert_eq!(CompactMove::from_bits(compact_move.bits()), Some(compact_move));
+                assert_eq!(CompactMove::from_json(&board_state, &compact_move.to_json(&board_state)), Some(compact_move));
+                compact_moves.push(compact_move);
+            }
+
+            compact_moves.sort();
+            compact_moves.dedup();
+            assert_eq!(compact_moves.len(), legal_moves.len());
+
+            match rng.choose(&legal_moves) {
+                Some(board_move) => board_state = board_state.apply_move(board_move),
+                None => board_state = BoardState::new(),
+            }
+        }
+    }
+
+    #[test]
+    fn compact_move_ignores_square_order() {
+        let board_state = BoardState::new();
+        let board_move = BoardMove { l_piece: [[0, 2], [0, 3], [1, 2], [2, 2]], neutral_pieces: [[1, 0], [3, 3]] };
+        let reordered = BoardMove { l_piece: [[2, 2], [1, 2], [0, 3], [0, 2]], neutral_pieces: [[3, 3], [1, 0]] };
+
+        let compact_move = CompactMove::from_board_move(&board_state, &board_move).unwrap();
+
+        assert_eq!(CompactMove::from_board_move(&board_state, &reordered), Some(compact_move));
+        assert_eq!(compact_move.neutral_piece_move(), Some((0, 1)));
+        assert_eq!(compact_move.to_board_move(&board_state), board_move);
+
+        // not an L, both neutral pieces moved, neutral pieces on one square
+        assert_eq!(CompactMove::from_board_move(&board_state, &BoardMove { l_piece: [[0, 2], [0, 3], [1, 3], [2, 2]], neutral_pieces: [[0, 0], [3, 3]] }), None);
+        assert_eq!(CompactMove::from_board_move(&board_state, &BoardMove { l_piece: [[0, 2], [0, 3], [1, 2], [2, 2]], neutral_pieces: [[1, 0], [2, 3]] }), None);
+        assert_eq!(CompactMove::from_board_move(&board_state, &BoardMove { l_piece: [[0, 2], [0, 3], [1, 2], [2, 2]], neutral_pieces: [[0, 0], [0, 0]] }), None);
+        assert_eq!(CompactMove::from_bits(48 << 8), None);
+        assert_eq!(CompactMove::from_bits(0x55), None);
+    }
+
+    #[test]
+    fn turns_count_down_to_turn_limit() {
+        let board_state = BoardState::new().with_max_turns(Some(3));
+        let board_move = board_state.legal_moves().next().unwrap();
+        let next_board_state = board_state.apply_move(&board_move);
+
+        assert_eq!((board_state.turn(), board_state.remaining_turns()), (1, Some(3)));
+        assert_eq!((next_board_state.turn(), next_board_state.remaining_turns()), (2, Some(2)));
+        assert_eq!(next_board_state.clone().with_turn(7).remaining_turns(), Some(0));
+        assert_eq!(BoardState::new().remaining_turns(), None);
+
+        // the same board with fewer turns left may be worth something else
+        assert_ne!(board_state.zobrist_hash(), board_state.clone().with_turn(2).zobrist_hash());
+        assert_eq!(BoardState::new().zobrist_hash(), BoardState::new().with_turn(2).zobrist_hash());
+    }
+
+    #[test]
+    fn unmake_move_restores_position() {
+        let mut rng = crate::rng::Rng::new(11);
+
+        for _ in 0..20 {
+            let mut board_state = BoardState::new();
+
+            for _ in 0..rng.below(30) {
+                match rng.choose(&board_state.calculate_legal_moves()) {
+                    Some(board_move) => board_state = board_state.apply_move(board_move),
+                    None => break,
+                }
+            }
+
+            let original = board_state.clone();
+
+            for board_move in original.legal_moves() {
+                let undo = board_state.make_move(&board_move);
+
+                assert_eq!(board_state, original.apply_move(&board_move));
+
+                board_state.unmake_move(undo);
+
+                assert_eq!(board_state, original);
+                assert_eq!(board_state.zobrist_hash(), original.zobrist_hash());
+            }
+        }
+    }
+
+    #[test]
+    fn trapped_player_has_no_optimal_move() {
+        assert_eq!(BoardState::from_board(TRAPPING_BOARD, 1, (3, 0)).calculate_optimal_move(1), None);
+    }
+
+    #[test]
+    fn optimal_move_traps_the_opponent() {
+        let current_board_state = BoardState::from_board(TRAPPING_BOARD, 2, (3, 0));
+
+        for depth in 0..2 {
+            let board_move = current_board_state.calculate_optimal_move(depth).unwrap();
+
+            assert_eq!(current_board_state.apply_move(&board_move).calculate_legal_moves(), vec![]);
+        }
+    }
+}
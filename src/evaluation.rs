@@ -0,0 +1,212 @@
+//! Static evaluation of positions the search doesn't look beyond, from the point of view of the player to move.
+
+use std::io;
+use std::path::Path;
+
+use crate::board_state::{BoardState, CORNERS, L_PIECE_MASKS};
+
+/// What a player's L-piece could do if it were its turn now.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Mobility {
+    /// Number of L-piece placements it can move to.
+    pub placements: u32,
+    /// Number of those placements that cover a corner, and therefore score.
+    pub corner_placements: u32,
+    /// Number of corners only the neutral pieces keep it from reaching.
+    pub blocked_corners: u32,
+}
+
+impl Mobility {
+    /// The mobility of the L-piece at `own`, with the other L-piece at `other`.
+    pub fn of(own: u16, other: u16, neutral_pieces: u16) -> Mobility {
+        let mut mobility = Mobility::default();
+        let mut corners = 0;
+        let mut corners_without_neutral_pieces = 0;
+
+        for &mask in L_PIECE_MASKS.iter().filter(|&&mask| mask != own && mask & other == 0) {
+            corners_without_neutral_pieces |= mask & CORNERS;
+
+            if mask & neutral_pieces == 0 {
+                mobility.placements += 1;
+                mobility.corner_placements += (mask & CORNERS != 0) as u32;
+                corners |= mask & CORNERS;
+            }
+        }
+
+        mobility.blocked_corners = (corners_without_neutral_pieces & !corners).count_ones();
+        mobility
+    }
+
+    /// The mobility of the player to move and of the other player.
+    pub fn of_players(board_state: &BoardState) -> (Mobility, Mobility) {
+        let l_pieces = board_state.l_pieces();
+        let own = l_pieces[board_state.current_player() as usize - 1];
+        let other = l_pieces[2 - board_state.current_player() as usize];
+
+        (Mobility::of(own, other, board_state.neutral_pieces()), Mobility::of(other, own, board_state.neutral_pieces()))
+    }
+
+    /// Grows from 0 towards 1 as the L-piece runs out of placements, and with it the player towards a loss.
+    fn trap_danger(&self) -> f32 {
+        1.0 / (self.placements + 1) as f32
+    }
+}
+
+/// How much each feature of a position counts; the score difference is in points.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct EvaluationWeights {
+    pub score: f32,
+    /// Per L-piece placement we have more than the opponent.
+    pub mobility: f32,
+    /// Per scoring placement we have more than the opponent.
+    pub corner_placements: f32,
+    /// Per corner the neutral pieces keep from the opponent more than from us.
+    pub blocked_corners: f32,
+    /// For the opponent being closer to trapped than we are, see `Mobility::trap_danger`.
+    pub trap_danger: f32,
+}
+
+/// Even at their extremes (47 placements, 24 of them scoring, and 4 blocked corners more than the opponent)
+/// the features together are worth less than a point, so they only break ties between equal scores.
+impl Default for EvaluationWeights {
+    fn default() -> Self {
+        EvaluationWeights { score: 1.0, mobility: 0.005, corner_placements: 0.01, blocked_corners: 0.04, trap_danger: 0.25 }
+    }
+}
+
+impl EvaluationWeights {
+    /// Just the score difference, which is what decides the game after the last turn.
+    pub const SCORE_ONLY: EvaluationWeights = EvaluationWeights { score: 1.0, mobility: 0.0, corner_placements: 0.0, blocked_corners: 0.0, trap_danger: 0.0 };
+
+    const NAMES: [&'static str; 5] = ["score", "mobility", "corner_placements", "blocked_corners", "trap_danger"];
+
+    fn weight_mut(&mut self, name: &str) -> Option<&mut f32> {
+        match name {
+            "score" => Some(&mut self.score),
+            "mobility" => Some(&mut self.mobility),
+            "corner_placements" => Some(&mut self.corner_placements),
+            "blocked_corners" => Some(&mut self.blocked_corners),
+            "trap_danger" => Some(&mut self.trap_danger),
+            _ => None,
+        }
+    }
+
+    /// Reads weights from a JSON object like `{"mobility": 0.02}`; left out weights keep their default.
+    pub fn from_json(weights_json: &json::JsonValue) -> Result<EvaluationWeights, String> {
+        if !weights_json.is_object() {
+            return Err("expected a JSON object of weights".to_string());
+        }
+
+        let mut weights = EvaluationWeights::default();
+
+        for (name, value) in weights_json.entries() {
+            let weight = weights.weight_mut(name).ok_or_else(|| format!("unknown weight \"{}\", expected one of {}", name, EvaluationWeights::NAMES.join(", ")))?;
+            *weight = value.as_f64().map(|value| value as f32).ok_or_else(|| format!("weight \"{}\" is not a number", name))?;
+        }
+
+        Ok(weights)
+    }
+
+    pub fn to_json(&self) -> json::JsonValue {
+        json::object! {
+            score: self.score,
+            mobility: self.mobility,
+            corner_placements: self.corner_placements,
+            blocked_corners: self.blocked_corners,
+            trap_danger: self.trap_danger,
+        }
+    }
+
+    /// Loads weights from a JSON file, see `from_json`.
+    pub fn load(path: impl AsRef<Path>) -> io::Result<EvaluationWeights> {
+        let weights_json = json::parse(&std::fs::read_to_string(path)?).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        EvaluationWeights::from_json(&weights_json).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+
+    /// The value of `board_state` for the player to move.
+    pub fn evaluate(&self, board_state: &BoardState) -> f32 {
+        let score = self.score * board_state.score_difference();
+
+        // counting placements is most of the work, and often not needed
+        if *self == EvaluationWeights::SCORE_ONLY {
+            return score;
+        }
+
+        let (own, other) = Mobility::of_players(board_state);
+
+        score
+            + self.mobility * (own.placements as f32 - other.placements as f32)
+            + self.corner_placements * (own.corner_placements as f32 - other.corner_placements as f32)
+            + self.blocked_corners * (other.blocked_corners as f32 - own.blocked_corners as f32)
+            + self.trap_danger * (other.trap_danger() - own.trap_danger())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board_state::{trapping_position, TRAPPING_BOARD};
+
+    #[test]
+    fn mobility_counts_placements() {
+        let board_state = trapping_position();
+        let (own, other) = Mobility::of_players(&board_state);
+
+        assert_eq!(own.placements as usize, board_state.calculate_legal_moves().iter().filter(|board_move| board_move.neutral_pieces == [[2, 0], [3, 3]]).count());
+        assert!(own.corner_placements > 0 && own.corner_placements < own.placements);
+
+        // player 1 is trapped: the neutral pieces block its way to two corners
+        assert_eq!(other.placements, 0);
+        assert_eq!(other.corner_placements, 0);
+        assert_eq!(other.blocked_corners, 2);
+
+        let (own, other) = Mobility::of_players(&BoardState::new());
+        assert_eq!(own, other);
+    }
+
+    #[test]
+    fn score_only_is_score_difference() {
+        let board_state = BoardState::from_board(TRAPPING_BOARD, 2, (3, 1));
+
+        assert_eq!(EvaluationWeights::SCORE_ONLY.evaluate(&board_state), -2.0);
+        assert_eq!(EvaluationWeights::SCORE_ONLY.evaluate(&BoardState::new()), 0.0);
+    }
+
+    #[test]
+    fn features_favour_the_freer_player() {
+        let weights = EvaluationWeights::default();
+        let board_state = BoardState::from_board(TRAPPING_BOARD, 2, (3, 1));
+        let evaluation = weights.evaluate(&board_state);
+
+        assert!(evaluation > -2.0 && evaluation < -1.0);
+        assert_eq!(weights.evaluate(&BoardState::new()), 0.0);
+
+        let features_only = EvaluationWeights { score: 0.0, ..weights };
+        assert!(features_only.evaluate(&board_state) > 0.0);
+        assert!(features_only.evaluate(&BoardState::from_board(board_state.board(), 1, (0, 0))) < 0.0);
+    }
+
+    #[test]
+    fn default_features_are_worth_less_than_a_point() {
+        let weights = EvaluationWeights::default();
+        let placements = L_PIECE_MASKS.len() as f32 - 1.0;
+        let corner_placements = L_PIECE_MASKS.iter().filter(|&&mask| mask & CORNERS != 0).count() as f32;
+        let blocked_corners = CORNERS.count_ones() as f32;
+
+        let most = weights.mobility * placements + weights.corner_placements * corner_placements + weights.blocked_corners * blocked_corners + weights.trap_danger;
+        assert!(most < 1.0, "features can be worth {}", most);
+    }
+
+    #[test]
+    fn weights_from_json() {
+        let weights = EvaluationWeights::from_json(&json::parse(r#"{"mobility": 0.5, "trap_danger": 2}"#).unwrap()).unwrap();
+
+        assert_eq!(weights, EvaluationWeights { mobility: 0.5, trap_danger: 2.0, ..EvaluationWeights::default() });
+        assert_eq!(EvaluationWeights::from_json(&weights.to_json()), Ok(weights));
+        assert_eq!(EvaluationWeights::from_json(&EvaluationWeights::default().to_json()), Ok(EvaluationWeights::default()));
+
+        assert!(EvaluationWeights::from_json(&json::parse(r#"{"mobilty": 0.5}"#).unwrap()).is_err());
+        assert!(EvaluationWeights::from_json(&json::parse(r#"{"mobility": "high"}"#).unwrap()).is_err());
+        assert!(EvaluationWeights::from_json(&json::parse("[0.5]").unwrap()).is_err());
+    }
+}
@@ -0,0 +1,12 @@
+pub mod board_state;
+pub mod evaluation;
+pub mod mcts;
+pub mod protocol;
+pub mod rng;
+pub mod search;
+pub mod strategy;
+pub mod symmetry;
+pub mod tablebase;
+pub mod tournament;
+pub mod transposition_table;
+mod zobrist;
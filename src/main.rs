@@ -1,174 +1,529 @@
-use json;
 use std::fmt;
 use std::io;
 use std::io::prelude::*;
-use std::{thread::sleep, time::Duration};
+use std::process::ExitCode;
+use std::sync::Arc;
+use std::time::Duration;
 
-mod board_state;
-use board_state::BoardState;
+use infinibattle_2022::board_state::{BoardMove, BoardState};
+use infinibattle_2022::evaluation::EvaluationWeights;
+use infinibattle_2022::protocol::{Command, MatchConfig, Message, ProtocolError};
+use infinibattle_2022::rng::Rng;
+use infinibattle_2022::strategy::{PonderingStrategy, SearchLimits, Strategy, StrategyKind};
+use infinibattle_2022::tablebase::Tablebase;
+
+/// Thinking time per turn, unless overridden with `--time-budget-ms`.
+const DEFAULT_TIME_BUDGET: Duration = Duration::from_millis(500);
+
+struct Bot {
+    strategy: PonderingStrategy,
+    limits: SearchLimits,
+    /// Whether to keep thinking while the opponent is, unless disabled with `--no-ponder`.
+    ponder: bool,
+    /// The last turn of the game from `--max-turns`, so the search knows when only the scores matter, unless
+    /// the arena sends it; `None` if unknown, which is safer than guessing too few turns.
+    max_turns: Option<u32>,
+}
+
+/// The bot that runs without arguments.
+impl Default for Bot {
+    fn default() -> Self {
+        Bot::from_args(std::iter::empty()).expect("the defaults are valid")
+    }
+}
+
+impl Bot {
+    fn from_args(mut args: impl Iterator<Item = String>) -> Result<Bot, String> {
+        let mut limits = SearchLimits { time: DEFAULT_TIME_BUDGET, ..SearchLimits::default() };
+        let mut strategy_kind = StrategyKind::Minimax;
+        let mut tablebase_path = None;
+        let mut seed = None;
+        let mut weights = EvaluationWeights::default();
+        let mut ponder = true;
+        let mut max_turns = None;
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--time-budget-ms" => {
+                    let value = args.next().ok_or("missing value for --time-budget-ms")?;
+                    let millis = value.parse().map_err(|err| format!("invalid value \"{}\" for --time-budget-ms: {}", value, err))?;
+                    limits.time = Duration::from_millis(millis);
+                },
+                "--strategy" => {
+                    strategy_kind = args.next().ok_or("missing value for --strategy")?.parse()?;
+                },
+                "--seed" => {
+                    let value = args.next().ok_or("missing value for --seed")?;
+                    seed = Some(value.parse().map_err(|err| format!("invalid value \"{}\" for --seed: {}", value, err))?);
+                },
+                "--tablebase" => {
+                    tablebase_path = Some(args.next().ok_or("missing value for --tablebase")?);
+                },
+                "--threads" => {
+                    let value = args.next().ok_or("missing value for --threads")?;
+                    limits.threads = value.parse().map_err(|err| format!("invalid value \"{}\" for --threads: {}", value, err))?;
+                    if limits.threads == 0 {
+                        return Err("--threads must be at least 1".to_string());
+                    }
+                },
+                "--weights" => {
+                    let path = args.next().ok_or("missing value for --weights")?;
+                    weights = EvaluationWeights::load(&path).map_err(|err| format!("cannot load weights \"{}\": {}", path, err))?;
+                },
+                "--max-turns" => {
+                    let value = args.next().ok_or("missing value for --max-turns")?;
+                    let turns = value.parse().map_err(|err| format!("invalid value \"{}\" for --max-turns: {}", value, err))?;
+                    max_turns = (turns > 0).then_some(turns);
+                },
+                "--no-ponder" => ponder = false,
+                _ => return Err(format!("unknown argument \"{}\"", arg)),
+            }
+        }
+
+        let tablebase = match tablebase_path {
+            Some(path) => Some(Tablebase::load_or_generate(&path).map_err(|err| format!("cannot load tablebase \"{}\": {}", path, err))?),
+            None if strategy_kind.uses_tablebase() => Some(Tablebase::generate()),
+            None => None,
+        };
+
+        let seed = seed.unwrap_or_else(|| Rng::from_time().next_u64());
+
+        Ok(Bot { strategy: PonderingStrategy::new(strategy_kind.create_with_weights(tablebase.map(Arc::new), seed, weights)), limits, ponder, max_turns })
+    }
+
+    /// `board_state` with the turn limit of the match.
+    fn with_turn_limit(&self, board_state: BoardState, match_config: &MatchConfig) -> BoardState {
+        board_state.with_max_turns(match_config.max_turns.or(self.max_turns))
+    }
+
+    /// The limits for a move in the match: with a turn timeout, we think for at most half of it, which leaves
+    /// room for stopping the search and answering in time.
+    fn limits(&self, match_config: &MatchConfig) -> SearchLimits {
+        match match_config.turn_timeout {
+            Some(turn_timeout) => SearchLimits { time: self.limits.time.min(turn_timeout / 2), ..self.limits },
+            None => self.limits,
+        }
+    }
+
+    /// The strategy's move, replaced by a legal one if it's illegal: an illegal move loses the game.
+    fn choose_move(&mut self, board_state: &BoardState, limits: &SearchLimits) -> Option<BoardMove> {
+        let board_move = self.strategy.choose_move(board_state, limits)?;
+
+        if let Some(report) = self.strategy.report() {
+            eprintln!("{}", report);
+        }
+
+        match board_state.validate_move(&board_move) {
+            Ok(()) => Some(board_move),
+            Err(reason) => {
+                eprintln!("strategy chose an illegal move ({}), playing the first legal move instead", reason);
+                board_state.legal_moves().next()
+            },
+        }
+    }
+
+    /// Carries out `action`, writing any commands to `output`.
+    fn perform(&mut self, action: Action, output: &mut impl Write) -> Result<(), BotError> {
+        match action {
+            Action::Send(command) => writeln!(output, "{}", command)?,
+            Action::PlayTurn(board_state, limits) => {
+                let board_move = self.choose_move(&board_state, &limits).ok_or(BotError::NoLegalMoves)?;
+                writeln!(output, "{}", Command::PlacePieces(board_move.clone()))?;
+                writeln!(output, "{}", Command::TurnEnd)?;
+
+                if self.ponder {
+                    self.strategy.start_pondering(&board_state.apply_move(&board_move));
+                }
+            },
+            Action::StopPondering => self.strategy.stop_pondering(),
+            Action::Warn(warning) => eprintln!("{}", warning),
+        }
+        Ok(())
+    }
+}
+
+/// What the bot has to do after a transition, besides moving to the next state.
+#[derive(Debug, PartialEq, Eq)]
+enum Action {
+    Send(Command),
+    /// Choose a move in the position within the limits, send it and end the turn.
+    PlayTurn(BoardState, SearchLimits),
+    StopPondering,
+    Warn(String),
+}
 
 #[derive(Debug)]
-enum Transition<'a> {
+enum Transition {
     AppInit,
-    GameInit(&'a io::Stdin),
-    GameStart,
-    TurnInit(&'a io::Stdin),
-    TurnStart,
-    Throw,
-    Sleep,
+    Received(Message),
+    /// A message that couldn't be read.
+    Unreadable(ProtocolError),
+    /// The arena closed our input.
+    EndOfInput,
 }
 
 #[derive(Debug)]
 enum State {
     AppIniting,
     GameIniting,
-    GameStarting,
-    TurnIniting,
-    TurnStarting(BoardState),
+    GameStarting(MatchConfig),
+    TurnIniting(MatchConfig),
+    TurnStarting(MatchConfig, BoardState),
+    /// Nothing more to do: the input has ended.
+    Finished,
 }
 
 impl fmt::Display for State {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{:?}", self)
+        // just the name, without the match and the board
+        match self {
+            State::AppIniting => write!(f, "AppIniting"),
+            State::GameIniting => write!(f, "GameIniting"),
+            State::GameStarting(_) => write!(f, "GameStarting"),
+            State::TurnIniting(_) => write!(f, "TurnIniting"),
+            State::TurnStarting(..) => write!(f, "TurnStarting"),
+            State::Finished => write!(f, "Finished"),
+        }
     }
 }
 
-impl<'a> fmt::Display for Transition<'a> {
+impl fmt::Display for Transition {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{:?}", self)
+        match self {
+            Transition::AppInit => write!(f, "app-init"),
+            // just the command, without the game state
+            Transition::Received(message) => write!(f, "{}", message.to_string().lines().next().unwrap_or_default()),
+            Transition::Unreadable(err) => write!(f, "{}", err),
+            Transition::EndOfInput => write!(f, "end of input"),
+        }
     }
 }
 
-impl<'a> Transition<'a> {
-    fn parse(line: &str, stdin: &'a io::Stdin) -> Transition<'a> {
-        use Transition::*;
-        match line {
-            "game-init" => GameInit(stdin),
-            "game-start" => GameStart,
-            "turn-init" => TurnInit(stdin),
-            "turn-start" => TurnStart,
-            "throw" => Throw,
-            "sleep" => Sleep,
-            _ => panic!("cannot parse Transition from line \"{}\"", line)
+/// Why the bot stopped before its input ended.
+#[derive(Debug)]
+enum BotError {
+    Protocol(ProtocolError),
+    Io(io::Error),
+    UnexpectedTransition { transition: String, state: String },
+    NoLegalMoves,
+    /// The arena asked us to fail.
+    Thrown,
+}
+
+impl fmt::Display for BotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BotError::Protocol(err) => write!(f, "{}", err),
+            BotError::Io(err) => write!(f, "i/o error: {}", err),
+            BotError::UnexpectedTransition { transition, state } => write!(f, "didn't expect {} in state {}", transition, state),
+            BotError::NoLegalMoves => write!(f, "cannot find any moves"),
+            BotError::Thrown => write!(f, "throwing on demand"),
         }
     }
 }
 
-fn parse_game_state_json(stdin: &io::Stdin) -> json::JsonValue {
-    let mut json_game_state = String::new();
-
-    for line in stdin.lock().lines() {
-        let line = line.unwrap();
-        json_game_state += &line;
-        // TODO: find better way to see if json input has ended...
-        if line.ends_with("}") {
-            break;
-        }
+impl From<ProtocolError> for BotError {
+    fn from(err: ProtocolError) -> Self {
+        BotError::Protocol(err)
     }
+}
 
-    match json::parse(&json_game_state) {
-        Ok(game_state) => game_state,
-        Err(err) => panic!("could not parse json: {}", err),
+impl From<io::Error> for BotError {
+    fn from(err: io::Error) -> Self {
+        BotError::Io(err)
     }
 }
 
 impl State {
-    fn next(self, transition: &Transition, output: &mut impl Write) -> Result<State, io::Error> {
+    /// The state after `transition`, and what to do about it. Doesn't read, write, search or ponder itself:
+    /// `run` carries out the actions.
+    fn next(self, transition: Transition, bot: &Bot) -> Result<(State, Vec<Action>), BotError> {
         use State::*;
         use Transition::*;
 
-        match (&self, transition) {
-            (AppIniting, AppInit) => {
-                writeln!(output, "bot-start")?;
-                output.flush()?;
-                io::stdout().flush()?;
-                Ok(GameIniting)
-            }
+        match (self, transition) {
+            (AppIniting, AppInit) => Ok((GameIniting, vec![Action::Send(Command::BotStart)])),
 
-            (GameIniting, GameInit(stdin)) => {
-                let _game_state = parse_game_state_json(stdin);
+            (GameIniting, Received(Message::GameInit(match_config))) => Ok((GameStarting(match_config), vec![])),
 
-                Ok(GameStarting)
-            },
+            (GameStarting(match_config), Received(Message::GameStart)) => Ok((TurnIniting(match_config), vec![])),
 
-            (GameStarting, GameStart) =>Ok(TurnIniting),
+            (TurnIniting(match_config), Received(Message::TurnInit(board_state))) => {
+                let mut actions = vec![Action::StopPondering];
 
-            (TurnIniting, TurnInit(stdin)) => {
-                let game_state = parse_game_state_json(stdin);
-
-                Ok(TurnStarting(BoardState::load(game_state)))
-            }
-            (TurnIniting, Sleep) => { sleep(Duration::from_secs(1)); Ok(TurnIniting) },
-
-            (TurnStarting(board_game_state), TurnStart) => {
-                if let Some(optimal_move) = board_game_state.calculate_optimal_move(0) {
-                    let place_pieces_command_json = json::object! {
-                        "PlayerLPieceCoordinates": [optimal_move.lPiece[0].to_vec(), optimal_move.lPiece[1].to_vec(), optimal_move.lPiece[2].to_vec(), optimal_move.lPiece[3].to_vec()].to_vec(),
-                        "NeutralPieceCoordinates": [optimal_move.neutralPieces[0].to_vec(), optimal_move.neutralPieces[1].to_vec()].to_vec()
-                    };
-                    writeln!(output, "{}", place_pieces_command_json)?;
-                } else {
-                    panic!("cannot find any moves");
+                // turn-init says whose turn it is, even if game-init seated us elsewhere
+                let player = board_state.current_player() - 1;
+                if match_config.player.is_some_and(|seat| seat != player) {
+                    actions.push(Action::Warn(format!("asked to move for player {} instead of our seat, playing for it", player)));
                 }
 
-                writeln!(output, "turn-end")?;
-                output.flush()?;
-                io::stdout().flush()?;
-                Ok(TurnIniting)
+                let board_state = bot.with_turn_limit(board_state, &match_config);
+                Ok((TurnStarting(match_config, board_state), actions))
+            },
+            (TurnIniting(match_config), Received(Message::Sleep)) => Ok((TurnIniting(match_config), vec![])),
+            // stopping would forfeit the rest of the match, so wait for the next turn-init instead
+            (TurnIniting(match_config), Unreadable(err)) if err.is_recoverable() => {
+                Ok((TurnIniting(match_config), vec![Action::Warn(format!("ignoring message: {}", err))]))
+            },
+
+            (TurnStarting(match_config, board_state), Received(Message::TurnStart)) => {
+                if board_state.legal_moves().next().is_none() {
+                    return Err(BotError::NoLegalMoves);
+                }
+                let limits = bot.limits(&match_config);
+                Ok((TurnIniting(match_config), vec![Action::PlayTurn(board_state, limits)]))
             },
-            (TurnStarting(_), Sleep) => { sleep(Duration::from_secs(1)); Ok(TurnIniting) }
+            (TurnStarting(match_config, _), Received(Message::Sleep)) => Ok((TurnIniting(match_config), vec![])),
+
+            (_, EndOfInput) => Ok((Finished, vec![Action::StopPondering])),
+
+            (_, Received(Message::Throw)) => Err(BotError::Thrown),
 
-            (_, Throw) => panic!("on demand!"),
+            (_, Unreadable(err)) => Err(BotError::Protocol(err)),
 
-            _ => panic!("didn't expect transition {} in state {}!", transition, &self),
+            (state, transition) => Err(BotError::UnexpectedTransition { transition: transition.to_string(), state: state.to_string() }),
         }
     }
 }
 
-fn main() {
-    let stdin = io::stdin();
-    let stdout = &mut io::stdout();
+/// Plays over `input` and `output`, stdin and stdout in a match, until the input ends.
+fn run(bot: &mut Bot, input: &mut impl BufRead, output: &mut impl Write) -> Result<(), BotError> {
     let mut state = State::AppIniting;
-
-    state = state.next(&Transition::AppInit, stdout).unwrap();
+    let mut transition = Transition::AppInit;
 
     loop {
-        let mut line = String::new();
-        if let Ok(_) = stdin.read_line(&mut line) {
-            let transition = Transition::parse(&line.trim_end(), &stdin);
-            state = state.next(&transition, stdout).unwrap();
+        let (next_state, actions) = state.next(transition, bot)?;
+        for action in actions {
+            bot.perform(action, output)?;
+        }
+        output.flush()?;
+
+        state = next_state;
+        if matches!(state, State::Finished) {
+            return Ok(());
+        }
+
+        transition = match Message::read(input) {
+            Ok(Some(message)) => Transition::Received(message),
+            Ok(None) => Transition::EndOfInput,
+            Err(err) => Transition::Unreadable(err),
+        };
+    }
+}
+
+/// Exits with 0 when the input ends, 1 when the bot stops because of an error, and 2 for invalid arguments.
+fn main() -> ExitCode {
+    let mut bot = match Bot::from_args(std::env::args().skip(1)) {
+        Ok(bot) => bot,
+        Err(err) => {
+            eprintln!("{}", err);
+            return ExitCode::from(2);
         }
+    };
+
+    match run(&mut bot, &mut io::stdin().lock(), &mut io::stdout().lock()) {
+        Ok(()) => {
+            eprintln!("end of input, shutting down");
+            ExitCode::SUCCESS
+        },
+        Err(err) => {
+            eprintln!("stopping: {}", err);
+            ExitCode::FAILURE
+        },
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use infinibattle_2022::board_state::LoadError;
+
+    fn match_config() -> MatchConfig {
+        MatchConfig { player: Some(0), initial_board: Some(BoardState::new()), ..MatchConfig::default() }
+    }
+
+    /// Carries out `actions` like `run` does, and returns the commands written.
+    fn perform(bot: &mut Bot, actions: Vec<Action>) -> Result<Vec<Command>, BotError> {
+        let mut output = vec![];
+        for action in actions {
+            bot.perform(action, &mut output)?;
+        }
+        Ok(String::from_utf8(output).unwrap().lines().map(|line| Command::parse(line).unwrap()).collect())
+    }
 
     #[test]
     #[should_panic]
     fn app_initing_cannot_sleep() {
-        State::AppIniting.next(&Transition::Sleep, &mut Vec::new()).unwrap();
+        State::AppIniting.next(Transition::Received(Message::Sleep), &Bot::default()).unwrap();
     }
 
     #[test]
     #[should_panic]
     fn game_initing_cannot_sleep() {
-        State::GameIniting.next(&Transition::Sleep, &mut Vec::new()).unwrap();
+        State::GameIniting.next(Transition::Received(Message::Sleep), &Bot::default()).unwrap();
     }
 
     #[test]
     #[should_panic]
     fn game_starting_cannot_sleep() {
-        State::GameStarting.next(&Transition::Sleep, &mut Vec::new()).unwrap();
+        State::GameStarting(match_config()).next(Transition::Received(Message::Sleep), &Bot::default()).unwrap();
     }
 
     #[test]
     fn app_initing_app_init_write_bot_start() {
-        let buffer = &mut Vec::new();
+        let (_, actions) = State::AppIniting.next(Transition::AppInit, &Bot::default()).unwrap();
+
+        assert_eq!(actions, vec![Action::Send(Command::BotStart)]);
+        assert_eq!(perform(&mut Bot::default(), actions).unwrap()[0].to_string(), "bot-start");
+    }
+
+    #[test]
+    fn plays_a_turn() {
+        let mut bot = Bot::default();
+        let mut state = State::AppIniting;
+        let mut commands = vec![];
+
+        for transition in [Transition::AppInit, Transition::Received(Message::GameInit(match_config())), Transition::Received(Message::GameStart), Transition::Received(Message::TurnInit(BoardState::new())), Transition::Received(Message::TurnStart)] {
+            let (next_state, actions) = state.next(transition, &bot).unwrap();
+            state = next_state;
+            commands.extend(perform(&mut bot, actions).unwrap());
+        }
+
+        assert!(matches!(state, State::TurnIniting(_)));
+        assert!(matches!(&commands[..], [Command::BotStart, Command::PlacePieces(board_move), Command::TurnEnd] if BoardState::new().is_legal_move(board_move)));
+    }
+
+    #[test]
+    fn turn_start_asks_for_a_move() {
+        let bot = Bot::from_args(["--strategy", "random", "--time-budget-ms", "800"].iter().map(|arg| arg.to_string())).unwrap();
+        let match_config = MatchConfig { turn_timeout: Some(Duration::from_millis(1000)), ..match_config() };
+        let (_, actions) = State::TurnStarting(match_config.clone(), BoardState::new()).next(Transition::Received(Message::TurnStart), &bot).unwrap();
+
+        // the search is left to `run`
+        assert_eq!(actions, vec![Action::PlayTurn(BoardState::new(), bot.limits(&match_config))]);
+    }
+
+    #[test]
+    fn ponders_until_turn_init() {
+        let mut bot = Bot::from_args(["--strategy", "minimax", "--time-budget-ms", "50"].iter().map(|arg| arg.to_string())).unwrap();
+        let (state, actions) = State::TurnStarting(match_config(), BoardState::new()).next(Transition::Received(Message::TurnStart), &bot).unwrap();
+        perform(&mut bot, actions).unwrap();
+
+        assert!(bot.strategy.is_pondering());
+
+        let (_, actions) = state.next(Transition::Received(Message::TurnInit(BoardState::new().with_turn(3))), &bot).unwrap();
+        assert_eq!(actions, vec![Action::StopPondering]);
+        perform(&mut bot, actions).unwrap();
+
+        assert!(!bot.strategy.is_pondering());
+
+        let mut bot = Bot::from_args(["--strategy", "minimax", "--time-budget-ms", "50", "--no-ponder"].iter().map(|arg| arg.to_string())).unwrap();
+        let (_, actions) = State::TurnStarting(match_config(), BoardState::new()).next(Transition::Received(Message::TurnStart), &bot).unwrap();
+        perform(&mut bot, actions).unwrap();
+
+        assert!(!bot.strategy.is_pondering());
+    }
+
+    #[test]
+    fn end_of_input_finishes() {
+        for state in [State::AppIniting, State::GameIniting, State::TurnIniting(match_config()), State::TurnStarting(match_config(), BoardState::new())] {
+            assert!(matches!(state.next(Transition::EndOfInput, &Bot::default()), Ok((State::Finished, actions)) if actions == vec![Action::StopPondering]));
+        }
+    }
 
-        State::AppIniting.next(&Transition::AppInit, buffer).unwrap();
+    #[test]
+    fn protocol_violations_are_errors() {
+        let next = |state: State, message| state.next(Transition::Received(message), &Bot::default());
+
+        assert!(matches!(next(State::GameIniting, Message::TurnStart), Err(BotError::UnexpectedTransition { .. })));
+        assert!(matches!(next(State::Finished, Message::TurnStart), Err(BotError::UnexpectedTransition { .. })));
+        assert!(matches!(next(State::TurnIniting(match_config()), Message::Throw), Err(BotError::Thrown)));
+
+        // `TRAPPING_BOARD` of board_state.rs; test helpers of the library aren't compiled into the binary's tests
+        let trapped = BoardState::from_board([0, 0, 4, 0, 2, 2, 2, 0, 1, 0, 2, 0, 1, 1, 1, 4], 1, (0, 0));
+        assert!(matches!(next(State::TurnStarting(match_config(), trapped), Message::TurnStart), Err(BotError::NoLegalMoves)));
+    }
+
+    #[test]
+    fn skips_unreadable_turn_init() {
+        let mut bot = Bot::from_args(["--strategy", "random", "--seed", "1"].iter().map(|arg| arg.to_string())).unwrap();
+        let input = format!("game-init\n{{}}\ngame-start\nturn-init\n{{\"GameState\": {{}}, \"Player\": 0}}\nturn-init\n{{\"Turn\": 1,,}}\n{}\nturn-start\n", Message::TurnInit(BoardState::new()));
+        let mut output = vec![];
+
+        run(&mut bot, &mut input.as_bytes(), &mut output).unwrap();
+
+        let commands: Vec<_> = String::from_utf8(output).unwrap().lines().map(|line| Command::parse(line).unwrap()).collect();
+        assert!(matches!(&commands[..], [Command::BotStart, Command::PlacePieces(board_move), Command::TurnEnd] if BoardState::new().is_legal_move(board_move)));
 
-        assert_eq!(buffer, "bot-start\n".as_bytes());
+        // elsewhere, or when the input can't be followed any more, it's still an error
+        let unreadable = || Transition::Unreadable(ProtocolError::InvalidGameState(LoadError::InvalidField("Player")));
+        assert!(matches!(State::GameIniting.next(unreadable(), &bot), Err(BotError::Protocol(_))));
+        assert!(matches!(State::TurnIniting(match_config()).next(Transition::Unreadable(ProtocolError::Io(io::ErrorKind::UnexpectedEof.into())), &bot), Err(BotError::Protocol(_))));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn plays_for_the_player_turn_init_names() {
+        let mut bot = Bot::default();
+        let other_seat = BoardState::from_board(BoardState::new().board(), 2, (0, 0));
+        let (state, actions) = State::TurnIniting(match_config()).next(Transition::Received(Message::TurnInit(other_seat.clone())), &bot).unwrap();
+
+        assert!(matches!(&state, State::TurnStarting(_, board_state) if *board_state == other_seat));
+        assert!(matches!(&actions[..], [Action::StopPondering, Action::Warn(_)]));
+
+        let (_, actions) = state.next(Transition::Received(Message::TurnStart), &bot).unwrap();
+        let commands = perform(&mut bot, actions).unwrap();
+        assert!(matches!(&commands[..], [Command::PlacePieces(board_move), Command::TurnEnd] if other_seat.is_legal_move(board_move)));
+    }
+
+    #[test]
+    fn match_config_sets_limits() {
+        let bot = Bot::from_args(["--strategy", "random", "--time-budget-ms", "800", "--max-turns", "50"].iter().map(|arg| arg.to_string())).unwrap();
+        let match_config = MatchConfig { max_turns: Some(30), turn_timeout: Some(Duration::from_millis(1000)), ..match_config() };
+
+        assert_eq!(bot.limits(&match_config).time, Duration::from_millis(500));
+        assert_eq!(bot.limits(&MatchConfig { turn_timeout: Some(Duration::from_secs(5)), ..match_config.clone() }).time, Duration::from_millis(800));
+        assert_eq!(bot.with_turn_limit(BoardState::new(), &match_config).max_turns(), Some(30));
+        assert_eq!(bot.with_turn_limit(BoardState::new(), &MatchConfig::default()).max_turns(), Some(50));
+        assert_eq!(Bot::default().with_turn_limit(BoardState::new(), &MatchConfig::default()).max_turns(), None);
+
+        // the match config stays with the states until the end
+        let (state, _) = State::GameIniting.next(Transition::Received(Message::GameInit(match_config.clone())), &bot).unwrap();
+        let (state, _) = state.next(Transition::Received(Message::GameStart), &bot).unwrap();
+        let (state, _) = state.next(Transition::Received(Message::TurnInit(BoardState::new())), &bot).unwrap();
+
+        assert!(matches!(&state, State::TurnStarting(config, board_state) if *config == match_config && board_state.max_turns() == Some(30)));
+    }
+
+    #[test]
+    fn bot_from_args() {
+        let bot = Bot::from_args(["--strategy", "random", "--time-budget-ms", "250", "--threads", "4"].iter().map(|arg| arg.to_string())).unwrap();
+
+        assert_eq!(bot.limits.time, Duration::from_millis(250));
+        assert_eq!(bot.limits.threads, 4);
+        assert_eq!(bot.max_turns, None);
+        assert_eq!(Bot::from_args(["--strategy", "random", "--max-turns", "40"].iter().map(|arg| arg.to_string())).unwrap().max_turns, Some(40));
+        assert_eq!(Bot::from_args(["--strategy", "random", "--max-turns", "0"].iter().map(|arg| arg.to_string())).unwrap().max_turns, None);
+        assert!(Bot::from_args(["--strategy", "alphazero"].iter().map(|arg| arg.to_string())).is_err());
+        assert!(Bot::from_args(["--seed", "-1"].iter().map(|arg| arg.to_string())).is_err());
+        assert!(Bot::from_args(["--time-budget-ms"].iter().map(|arg| arg.to_string())).is_err());
+        assert!(Bot::from_args(["--threads", "0"].iter().map(|arg| arg.to_string())).is_err());
+        assert!(Bot::from_args(["--weights", "/nonexistent/weights.json"].iter().map(|arg| arg.to_string())).is_err());
+        assert!(Bot::from_args(["--fast"].iter().map(|arg| arg.to_string())).is_err());
+    }
+
+    #[test]
+    fn seeded_random_bot_is_reproducible() {
+        let args = || ["--strategy", "random", "--seed", "2022"].iter().map(|arg| arg.to_string());
+        let mut bot = Bot::from_args(args()).unwrap();
+        let mut other_bot = Bot::from_args(args()).unwrap();
+        let mut board_state = BoardState::new();
+
+        for _ in 0..10 {
+            let board_move = bot.strategy.choose_move(&board_state, &bot.limits).unwrap();
+
+            assert_eq!(other_bot.strategy.choose_move(&board_state, &other_bot.limits), Some(board_move.clone()));
+
+            board_state = board_state.apply_move(&board_move);
+        }
+    }
+}
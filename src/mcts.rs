@@ -0,0 +1,435 @@
+use std::mem;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::board_state::{BoardMove, BoardState, CompactMove};
+use crate::rng::Rng;
+
+/// When `Mcts::search` stops.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MctsBudget {
+    Iterations(u32),
+    Time(Duration),
+}
+
+/// How moves are picked while playing out a game from a new node.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Rollout {
+    #[default]
+    Random,
+    /// Random, but preferring moves whose L-piece occupies a corner and scores.
+    Heuristic,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct MctsConfig {
+    pub budget: MctsBudget,
+    pub rollout: Rollout,
+    /// The UCT exploration constant.
+    pub exploration: f32,
+    /// Rollouts that don't trap a player within this many plies are scored by the score difference.
+    pub max_rollout_plies: u32,
+    /// Bytes the tree may take. A full tree isn't expanded any more: searching keeps playing out games from its
+    /// leaves, and pondering stops.
+    pub max_tree_memory: usize,
+}
+
+impl Default for MctsConfig {
+    fn default() -> Self {
+        MctsConfig { budget: MctsBudget::Time(Duration::from_millis(500)), rollout: Rollout::default(), exploration: std::f32::consts::SQRT_2, max_rollout_plies: 60, max_tree_memory: TREE_MEMORY_BUDGET }
+    }
+}
+
+/// Default of `MctsConfig::max_tree_memory`.
+pub const TREE_MEMORY_BUDGET: usize = 64 << 20;
+
+#[derive(Clone, Debug)]
+struct Node {
+    board_state: BoardState,
+    children: Vec<usize>,
+    /// Moves without a child node yet.
+    untried_moves: Vec<CompactMove>,
+    /// The move from the parent into this node.
+    board_move: Option<CompactMove>,
+    visits: u32,
+    /// Sum of the rewards of the player who moved into this node.
+    reward: f32,
+}
+
+impl Node {
+    fn new(board_state: BoardState, board_move: Option<CompactMove>) -> Node {
+        let mut untried_moves: Vec<CompactMove> = board_state.legal_moves().filter_map(|board_move| CompactMove::from_board_move(&board_state, &board_move)).collect();
+        untried_moves.shrink_to_fit();
+        Node { board_state, children: vec![], untried_moves, board_move, visits: 0, reward: 0.0 }
+    }
+
+    /// Bytes the node takes, including its lists.
+    fn memory(&self) -> usize {
+        mem::size_of::<Node>() + self.children.capacity() * mem::size_of::<usize>() + self.untried_moves.capacity() * mem::size_of::<CompactMove>()
+    }
+}
+
+/// Monte Carlo Tree Search with UCT selection. The tree is kept between searches, so when the next position
+/// is found in it (after our move and the opponent's reply) the earlier work is reused.
+#[derive(Clone, Debug)]
+pub struct Mcts {
+    pub config: MctsConfig,
+    nodes: Vec<Node>,
+    /// Sum of `Node::memory` over the tree.
+    memory: usize,
+    /// Whether an expansion didn't fit in the memory budget.
+    full: bool,
+    rng: Rng,
+    iterations: u32,
+}
+
+impl Mcts {
+    pub fn new(config: MctsConfig, seed: u64) -> Mcts {
+        Mcts { config, nodes: vec![], memory: 0, full: false, rng: Rng::new(seed), iterations: 0 }
+    }
+
+    /// Number of iterations of the last search.
+    pub fn iterations(&self) -> u32 {
+        self.iterations
+    }
+
+    pub fn tree_size(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Bytes the tree takes, which stays within `MctsConfig::max_tree_memory`.
+    pub fn tree_memory(&self) -> usize {
+        self.memory
+    }
+
+    pub fn search(&mut self, board_state: &BoardState) -> Option<BoardMove> {
+        self.reuse_tree(board_state);
+
+        if self.nodes[0].untried_moves.is_empty() && self.nodes[0].children.is_empty() {
+            return None;
+        }
+
+        let start = Instant::now();
+        self.iterations = 0;
+
+        loop {
+            let done = match self.config.budget {
+                MctsBudget::Iterations(iterations) => self.iterations >= iterations,
+                MctsBudget::Time(time) => self.iterations > 0 && start.elapsed() >= time,
+            };
+            if done {
+                break;
+            }
+
+            self.iterate();
+            self.iterations += 1;
+        }
+
+        self.nodes[0]
+            .children
+            .iter()
+            .max_by_key(|child| self.nodes[**child].visits)
+            .and_then(|child| self.nodes[*child].board_move)
+            .map(|board_move| board_move.to_board_move(board_state))
+    }
+
+    /// Grows the tree of `board_state` until `stop` is set, so the next `search` from a position in it (after
+    /// the opponent's reply) starts with more iterations. Stops early when the tree is full.
+    pub fn ponder(&mut self, board_state: &BoardState, stop: &AtomicBool) {
+        self.reuse_tree(board_state);
+
+        if self.nodes[0].untried_moves.is_empty() && self.nodes[0].children.is_empty() {
+            return;
+        }
+
+        while !stop.load(Ordering::Relaxed) && !self.full {
+            self.iterate();
+        }
+    }
+
+    /// Makes the node with `board_state` the root: the current root, one of its children or grandchildren,
+    /// or a new node if it isn't in the tree or the kept tree is over the memory budget.
+    fn reuse_tree(&mut self, board_state: &BoardState) {
+        let mut candidates = vec![];
+        if !self.nodes.is_empty() {
+            candidates.push(0);
+            for child in &self.nodes[0].children {
+                candidates.push(*child);
+                candidates.extend(&self.nodes[*child].children);
+            }
+        }
+
+        match candidates.into_iter().find(|index| self.nodes[*index].board_state == *board_state) {
+            Some(0) => {},
+            Some(index) => self.reroot(index),
+            None => self.nodes.clear(),
+        }
+
+        // the budget may have been lowered since the tree was grown
+        if self.memory > self.config.max_tree_memory {
+            self.nodes.clear();
+        }
+
+        if self.nodes.is_empty() {
+            self.nodes = vec![Node::new(board_state.clone(), None)];
+        }
+        self.memory = self.nodes.iter().map(Node::memory).sum();
+        self.full = false;
+    }
+
+    fn reroot(&mut self, index: usize) {
+        let mut old_nodes: Vec<Option<Node>> = std::mem::take(&mut self.nodes).into_iter().map(Some).collect();
+        let mut stack: Vec<(usize, Option<usize>)> = vec![(index, None)];
+
+        while let Some((old_index, parent)) = stack.pop() {
+            let mut node = old_nodes[old_index].take().unwrap();
+            let children = std::mem::take(&mut node.children);
+            let new_index = self.nodes.len();
+            self.nodes.push(node);
+
+            if let Some(parent) = parent {
+                self.nodes[parent].children.push(new_index);
+            }
+            stack.extend(children.into_iter().map(|child| (child, Some(new_index))));
+        }
+
+        self.nodes[0].board_move = None;
+    }
+
+    fn iterate(&mut self) {
+        let mut path = vec![0];
+        let mut index = 0;
+
+        while self.nodes[index].untried_moves.is_empty() && !self.nodes[index].children.is_empty() {
+            index = self.select_child(index);
+            path.push(index);
+        }
+
+        if !self.nodes[index].untried_moves.is_empty() && !self.full {
+            let move_index = self.rng.below(self.nodes[index].untried_moves.len());
+            let board_move = self.nodes[index].untried_moves[move_index];
+            let board_state = &self.nodes[index].board_state;
+            let child = Node::new(board_state.apply_move(&board_move.to_board_move(board_state)), Some(board_move));
+
+            // grow the children by hand, so the memory they take is known up front
+            let children = &self.nodes[index].children;
+            let children_growth = if children.len() == children.capacity() { children.len().max(4) } else { 0 };
+            let memory = self.memory + child.memory() + children_growth * mem::size_of::<usize>();
+
+            if memory > self.config.max_tree_memory {
+                self.full = true;
+            } else {
+                self.nodes[index].untried_moves.swap_remove(move_index);
+                self.nodes[index].children.reserve_exact(children_growth);
+                self.nodes.push(child);
+                let child_index = self.nodes.len() - 1;
+                self.nodes[index].children.push(child_index);
+                self.memory = memory;
+                path.push(child_index);
+                index = child_index;
+            }
+        }
+
+        let board_state = self.nodes[index].board_state.clone();
+        let reward = self.rollout(board_state);
+
+        for index in path {
+            let node = &mut self.nodes[index];
+            node.visits += 1;
+            node.reward += if node.board_state.current_player() == 2 { reward } else { 1.0 - reward };
+        }
+    }
+
+    fn select_child(&self, index: usize) -> usize {
+        let log_visits = (self.nodes[index].visits.max(1) as f32).ln();
+        let uct = |child: &usize| {
+            let child = &self.nodes[*child];
+            child.reward / child.visits as f32 + self.config.exploration * (log_visits / child.visits as f32).sqrt()
+        };
+
+        *self.nodes[index].children.iter().max_by(|a, b| uct(a).total_cmp(&uct(b))).unwrap()
+    }
+
+    /// Plays out the game and returns the reward for player 1: 1 for a win, 0 for a loss, and in between
+    /// depending on the score difference if nobody gets trapped.
+    fn rollout(&mut self, mut board_state: BoardState) -> f32 {
+        for _ in 0..self.config.max_rollout_plies {
+            // at the turn limit the scores decide
+            if board_state.remaining_turns() == Some(0) {
+                let (score_player_1, score_player_2) = board_state.scores();
+                return 0.5 + 0.5 * (score_player_1 - score_player_2).signum() as f32;
+            }
+
+            let legal_moves = board_state.calculate_legal_moves();
+
+            if legal_moves.is_empty() {
+                return if board_state.current_player() == 1 { 0.0 } else { 1.0 };
+            }
+
+            let board_move = match self.config.rollout {
+                Rollout::Random => self.rng.choose(&legal_moves),
+                Rollout::Heuristic => {
+                    let scoring_moves: Vec<&BoardMove> = legal_moves.iter().filter(|board_move| board_move.occupies_corner()).collect();
+                    match self.rng.choose(&scoring_moves) {
+                        Some(board_move) => Some(*board_move),
+                        None => self.rng.choose(&legal_moves),
+                    }
+                },
+            };
+
+            board_state.make_move(board_move.unwrap());
+        }
+
+        let (score_player_1, score_player_2) = board_state.scores();
+        0.5 + 0.5 * ((score_player_1 - score_player_2) as f32 / 4.0).tanh()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board_state::{trapping_position, TRAPPING_BOARD};
+
+    fn config(budget: MctsBudget, rollout: Rollout) -> MctsConfig {
+        MctsConfig { budget, rollout, max_rollout_plies: 10, ..MctsConfig::default() }
+    }
+
+    #[test]
+    fn mcts_finds_winning_move() {
+        for rollout in [Rollout::Random, Rollout::Heuristic] {
+            let mut mcts = Mcts::new(config(MctsBudget::Iterations(1500), rollout), 1);
+            let board_state = trapping_position();
+
+            let board_move = mcts.search(&board_state).unwrap();
+
+            assert_eq!(board_state.apply_move(&board_move).calculate_legal_moves(), vec![]);
+            assert_eq!(mcts.iterations(), 1500);
+        }
+    }
+
+    #[test]
+    fn mcts_plays_for_the_scores_in_the_last_turn() {
+        let board_state = trapping_position().with_turn(9).with_max_turns(Some(9));
+        let mut mcts = Mcts::new(config(MctsBudget::Iterations(500), Rollout::Random), 3);
+
+        assert!(mcts.search(&board_state).unwrap().occupies_corner());
+    }
+
+    #[test]
+    fn mcts_is_deterministic_for_seed() {
+        let board_state = BoardState::new();
+
+        let board_move = Mcts::new(config(MctsBudget::Iterations(200), Rollout::Random), 7).search(&board_state);
+
+        assert_eq!(Mcts::new(config(MctsBudget::Iterations(200), Rollout::Random), 7).search(&board_state), board_move);
+    }
+
+    #[test]
+    fn mcts_has_no_move_when_trapped() {
+        let board_state = BoardState::from_board(TRAPPING_BOARD, 1, (0, 0));
+
+        assert_eq!(Mcts::new(MctsConfig::default(), 0).search(&board_state), None);
+    }
+
+    #[test]
+    fn mcts_respects_time_budget() {
+        let mut mcts = Mcts::new(config(MctsBudget::Time(Duration::from_millis(100)), Rollout::Random), 0);
+        let start = Instant::now();
+
+        assert!(mcts.search(&BoardState::new()).is_some());
+        assert!(start.elapsed() < Duration::from_secs(5));
+        assert!(mcts.iterations() > 0);
+    }
+
+    #[test]
+    fn mcts_respects_iteration_budget() {
+        let mut mcts = Mcts::new(config(MctsBudget::Iterations(50), Rollout::Random), 0);
+
+        assert!(mcts.search(&BoardState::new()).is_some());
+        assert_eq!(mcts.iterations(), 50);
+    }
+
+    #[test]
+    fn pondering_grows_tree_until_stopped() {
+        let mut mcts = Mcts::new(config(MctsBudget::Iterations(10), Rollout::Random), 5);
+        let board_state = BoardState::new();
+        let stop = AtomicBool::new(false);
+
+        std::thread::scope(|scope| {
+            scope.spawn(|| {
+                std::thread::sleep(Duration::from_millis(20));
+                stop.store(true, Ordering::Relaxed);
+            });
+            mcts.ponder(&board_state, &stop);
+        });
+
+        let pondered_visits = mcts.nodes[0].visits;
+        assert!(pondered_visits > 10);
+
+        // the search continues from the pondered tree
+        mcts.search(&board_state).unwrap();
+        assert_eq!(mcts.nodes[0].visits, pondered_visits + 10);
+    }
+
+    #[test]
+    fn pondering_stops_when_tree_is_full() {
+        let mut mcts = Mcts::new(MctsConfig { max_tree_memory: 64 << 10, ..config(MctsBudget::Iterations(10), Rollout::Random) }, 5);
+
+        mcts.ponder(&BoardState::new(), &AtomicBool::new(false));
+
+        assert!(mcts.tree_memory() > 63 << 10);
+        assert!(mcts.tree_memory() <= 64 << 10);
+        assert_eq!(mcts.tree_memory(), mcts.nodes.iter().map(Node::memory).sum::<usize>());
+    }
+
+    #[test]
+    fn search_stops_growing_full_tree() {
+        let mut mcts = Mcts::new(MctsConfig { max_tree_memory: 64 << 10, ..config(MctsBudget::Iterations(2000), Rollout::Random) }, 5);
+        let board_state = BoardState::new();
+
+        assert!(mcts.search(&board_state).is_some());
+        assert!(mcts.tree_memory() > 63 << 10);
+        assert!(mcts.tree_memory() <= 64 << 10);
+
+        // a full tree doesn't grow, but its visits still add up
+        mcts.search(&board_state).unwrap();
+        assert!(mcts.tree_memory() <= 64 << 10);
+        assert_eq!(mcts.tree_memory(), mcts.nodes.iter().map(Node::memory).sum::<usize>());
+        assert_eq!(mcts.nodes[0].visits, 4000);
+    }
+
+    #[test]
+    fn reused_tree_over_budget_restarts() {
+        let mut mcts = Mcts::new(config(MctsBudget::Iterations(500), Rollout::Random), 3);
+        let board_state = BoardState::new();
+
+        mcts.search(&board_state).unwrap();
+        mcts.config.max_tree_memory = mcts.tree_memory() / 2;
+        mcts.search(&board_state).unwrap();
+
+        assert_eq!(mcts.nodes[0].visits, 500);
+        assert!(mcts.tree_memory() <= mcts.config.max_tree_memory);
+    }
+
+    #[test]
+    fn mcts_reuses_tree_after_reply() {
+        let mut mcts = Mcts::new(config(MctsBudget::Iterations(500), Rollout::Random), 3);
+        let board_state = BoardState::new();
+
+        let board_move = mcts.search(&board_state).unwrap();
+        let our_node = *mcts.nodes[0].children.iter().find(|child| mcts.nodes[**child].board_move.map(|child_move| child_move.to_board_move(&board_state)) == Some(board_move.clone())).unwrap();
+        let reply_node = mcts.nodes[our_node].children[0];
+        let reply_state = mcts.nodes[reply_node].board_state.clone();
+        let reply_visits = mcts.nodes[reply_node].visits;
+
+        mcts.config.budget = MctsBudget::Iterations(10);
+        mcts.search(&reply_state).unwrap();
+
+        assert_eq!(mcts.nodes[0].board_state, reply_state);
+        assert_eq!(mcts.nodes[0].visits, reply_visits + 10);
+
+        // an unknown position starts a new tree
+        mcts.search(&trapping_position()).unwrap();
+        assert_eq!(mcts.nodes[0].visits, 10);
+    }
+}
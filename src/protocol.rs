@@ -0,0 +1,439 @@
+//! The messages of the match protocol: what the arena sends the bots, and what they answer.
+
+use std::fmt;
+use std::io::{self, BufRead};
+use std::time::Duration;
+
+use crate::board_state::{BoardMove, BoardState, LoadError};
+
+/// Collects lines until they hold a complete JSON object or array, so a message may span any number of lines.
+///
+/// Brackets are counted outside of strings only, so braces in string values and escaped quotes don't end the
+/// message early.
+#[derive(Clone, Debug, Default)]
+pub struct JsonFramer {
+    buffer: String,
+    depth: u32,
+    started: bool,
+    in_string: bool,
+    escaped: bool,
+}
+
+impl JsonFramer {
+    /// Adds a line, and returns the message once it is complete. Blank lines before a message are skipped.
+    pub fn push_line(&mut self, line: &str) -> Option<String> {
+        if !self.started && line.trim().is_empty() {
+            return None;
+        }
+
+        for character in line.chars() {
+            if self.in_string {
+                match character {
+                    _ if self.escaped => self.escaped = false,
+                    '\\' => self.escaped = true,
+                    '"' => self.in_string = false,
+                    _ => {},
+                }
+                continue;
+            }
+
+            match character {
+                '"' => self.in_string = true,
+                '{' | '[' => {
+                    self.depth += 1;
+                    self.started = true;
+                },
+                '}' | ']' => self.depth = self.depth.saturating_sub(1),
+                _ => {},
+            }
+        }
+
+        self.buffer.push_str(line.trim_end_matches(['\r', '\n']));
+        self.buffer.push('\n');
+
+        // a line that doesn't open an object or array is a message by itself, for the parser to accept or reject
+        if (!self.started || self.depth == 0) && !self.in_string {
+            let message = std::mem::take(&mut self.buffer);
+            *self = JsonFramer::default();
+            Some(message)
+        } else {
+            None
+        }
+    }
+
+    /// Whether part of a message has been read.
+    pub fn is_partial(&self) -> bool {
+        !self.buffer.is_empty()
+    }
+}
+
+/// Reads one JSON message, which may span several lines.
+pub fn read_json_message(reader: &mut impl BufRead) -> io::Result<json::JsonValue> {
+    let mut framer = JsonFramer::default();
+    let mut line = String::new();
+
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            let message = if framer.is_partial() { "end of input in the middle of a JSON message" } else { "end of input instead of a JSON message" };
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, message));
+        }
+
+        if let Some(message) = framer.push_line(&line) {
+            return json::parse(&message).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err));
+        }
+    }
+}
+
+/// The setup of a match, from the game-init payload. Every field is optional: whatever the payload holds is
+/// read as far as it's understood.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MatchConfig {
+    /// Our player index: 0 or 1, as in the protocol, if the arena sends it as "Player".
+    pub player: Option<u8>,
+    /// The position the game starts from, with player 0 to move, if the payload holds a valid game state.
+    pub initial_board: Option<BoardState>,
+    /// The last turn, after which the scores decide, if the arena sends it as "MaxTurns".
+    pub max_turns: Option<u32>,
+    /// How long a bot may take for a turn, if the arena sends it as "TurnTimeoutMs".
+    pub turn_timeout: Option<Duration>,
+    /// The fields of the payload that aren't known or valid here, as an object, so they can be passed on as they are;
+    /// a payload that isn't an object is kept here as a whole.
+    pub extra: json::JsonValue,
+}
+
+impl Default for MatchConfig {
+    fn default() -> Self {
+        MatchConfig { player: None, initial_board: None, max_turns: None, turn_timeout: None, extra: json::JsonValue::new_object() }
+    }
+}
+
+impl MatchConfig {
+    pub fn load(match_config_json: json::JsonValue) -> MatchConfig {
+        if !match_config_json.is_object() {
+            return MatchConfig { extra: match_config_json, ..MatchConfig::default() };
+        }
+
+        let player = match match_config_json["Player"].as_u8() {
+            Some(player @ (0 | 1)) => Some(player),
+            _ => None,
+        };
+        let max_turns = match_config_json["MaxTurns"].as_u32();
+        let turn_timeout = match_config_json["TurnTimeoutMs"].as_u64().map(Duration::from_millis);
+
+        // "Player" is our seat rather than the side to move, and the game starts with player 0
+        let game_state = json::object! { "GameState": match_config_json["GameState"].clone(), "Turn": match_config_json["Turn"].clone(), "Player": 0 };
+        let initial_board = BoardState::load(game_state).ok();
+
+        let understood = |name: &str| match name {
+            "Player" => player.is_some(),
+            "MaxTurns" => max_turns.is_some(),
+            "TurnTimeoutMs" => turn_timeout.is_some(),
+            "GameState" | "Turn" => initial_board.is_some(),
+            _ => false,
+        };
+
+        let mut extra = json::JsonValue::new_object();
+        for (name, value) in match_config_json.entries().filter(|(name, _)| !understood(name)) {
+            extra[name] = value.clone();
+        }
+
+        MatchConfig { player, initial_board, max_turns, turn_timeout, extra }
+    }
+
+    pub fn to_json(&self) -> json::JsonValue {
+        if !self.extra.is_object() {
+            return self.extra.clone();
+        }
+
+        let mut match_config_json = self.initial_board.as_ref().map_or_else(json::JsonValue::new_object, BoardState::to_json);
+
+        match_config_json.remove("Player");
+        if let Some(player) = self.player {
+            match_config_json["Player"] = player.into();
+        }
+        if let Some(max_turns) = self.max_turns {
+            match_config_json["MaxTurns"] = max_turns.into();
+        }
+        if let Some(turn_timeout) = self.turn_timeout {
+            match_config_json["TurnTimeoutMs"] = (turn_timeout.as_millis() as u64).into();
+        }
+        for (name, value) in self.extra.entries() {
+            match_config_json[name] = value.clone();
+        }
+
+        match_config_json
+    }
+}
+
+/// A message from the arena to a bot.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Message {
+    GameInit(MatchConfig),
+    GameStart,
+    TurnInit(BoardState),
+    TurnStart,
+    /// Asks the bot to fail, to test error handling.
+    Throw,
+    /// The opponent is thinking.
+    Sleep,
+}
+
+/// Why a message could not be read.
+#[derive(Debug)]
+pub enum ProtocolError {
+    Io(io::Error),
+    UnknownCommand(String),
+    InvalidGameState(LoadError),
+}
+
+impl fmt::Display for ProtocolError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProtocolError::Io(err) => write!(f, "cannot read message: {}", err),
+            ProtocolError::UnknownCommand(line) => write!(f, "unknown command \"{}\"", line),
+            ProtocolError::InvalidGameState(err) => write!(f, "invalid game state: {}", err),
+        }
+    }
+}
+
+impl ProtocolError {
+    /// Whether the message was read in full but is invalid, so the next one can still be read.
+    pub fn is_recoverable(&self) -> bool {
+        match self {
+            ProtocolError::Io(err) => err.kind() == io::ErrorKind::InvalidData,
+            ProtocolError::UnknownCommand(_) | ProtocolError::InvalidGameState(_) => true,
+        }
+    }
+}
+
+impl std::error::Error for ProtocolError {}
+
+impl From<io::Error> for ProtocolError {
+    fn from(err: io::Error) -> Self {
+        ProtocolError::Io(err)
+    }
+}
+
+impl Message {
+    /// Reads the next message, skipping blank lines; `None` at the end of the input.
+    pub fn read(reader: &mut impl BufRead) -> Result<Option<Message>, ProtocolError> {
+        let mut line = String::new();
+
+        loop {
+            line.clear();
+            if reader.read_line(&mut line)? == 0 {
+                return Ok(None);
+            }
+            if !line.trim().is_empty() {
+                break;
+            }
+        }
+
+        let message = match line.trim() {
+            "game-init" => Message::GameInit(MatchConfig::load(read_json_message(reader)?)),
+            "game-start" => Message::GameStart,
+            "turn-init" => Message::TurnInit(BoardState::load(read_json_message(reader)?).map_err(ProtocolError::InvalidGameState)?),
+            "turn-start" => Message::TurnStart,
+            "throw" => Message::Throw,
+            "sleep" => Message::Sleep,
+            line => return Err(ProtocolError::UnknownCommand(line.to_string())),
+        };
+
+        Ok(Some(message))
+    }
+}
+
+/// The lines of the message, without a final newline.
+impl fmt::Display for Message {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Message::GameInit(match_config) => write!(f, "game-init\n{}", match_config.to_json().dump()),
+            Message::GameStart => write!(f, "game-start"),
+            Message::TurnInit(board_state) => write!(f, "turn-init\n{}", board_state.to_json().dump()),
+            Message::TurnStart => write!(f, "turn-start"),
+            Message::Throw => write!(f, "throw"),
+            Message::Sleep => write!(f, "sleep"),
+        }
+    }
+}
+
+/// A line from a bot to the arena.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Command {
+    BotStart,
+    PlacePieces(BoardMove),
+    TurnEnd,
+}
+
+impl Command {
+    /// `None` if the line is not a command.
+    pub fn parse(line: &str) -> Option<Command> {
+        match line.trim() {
+            "bot-start" => Some(Command::BotStart),
+            "turn-end" => Some(Command::TurnEnd),
+            line => BoardMove::from_json(&json::parse(line).ok()?).map(Command::PlacePieces),
+        }
+    }
+}
+
+impl fmt::Display for Command {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Command::BotStart => write!(f, "bot-start"),
+            Command::PlacePieces(board_move) => write!(f, "{}", board_move.to_json().dump()),
+            Command::TurnEnd => write!(f, "turn-end"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GAME_STATE: &str = r#"{"GameState":{"Board":{"Board":[[4,0,0,0],[1,2,2,2],[1,1,1,2],[0,0,0,4]]},"ScorePlayer0":0,"ScorePlayer1":0},"Turn":2,"Player":0}"#;
+
+    fn read(input: &str) -> io::Result<json::JsonValue> {
+        read_json_message(&mut input.as_bytes())
+    }
+
+    #[test]
+    fn reads_single_line_message() {
+        let input = format!("{}\nturn-start\n", GAME_STATE);
+        let mut reader = input.as_bytes();
+
+        assert_eq!(read_json_message(&mut reader).unwrap(), json::parse(GAME_STATE).unwrap());
+
+        // the next line is left for the protocol
+        let mut rest = String::new();
+        reader.read_line(&mut rest).unwrap();
+        assert_eq!(rest, "turn-start\n");
+    }
+
+    #[test]
+    fn reads_pretty_printed_message() {
+        let pretty = r#"{
+            "GameState": {
+              "Board": {
+                "Board": [
+                  [4, 0, 0, 0],
+                  [1, 2, 2, 2],
+                  [1, 1, 1, 2],
+                  [0, 0, 0, 4]
+                ]
+              },
+              "ScorePlayer0": 0,
+              "ScorePlayer1": 0
+            },
+            "Turn": 2,
+            "Player": 0
+          }
+turn-start
+"#;
+
+        assert_eq!(read(pretty).unwrap(), json::parse(GAME_STATE).unwrap());
+    }
+
+    #[test]
+    fn reads_whitespace_padded_message() {
+        let padded = format!("\n   \r\n  {}  \t\r\nturn-start\n", GAME_STATE.replace(",", " ,\r\n  "));
+
+        assert_eq!(read(&padded).unwrap(), json::parse(GAME_STATE).unwrap());
+    }
+
+    #[test]
+    fn brackets_in_strings_are_ignored() {
+        let message = "{\"Name\": \"}{ \\\" ]\",\n\"Turn\": 1}\n";
+
+        assert_eq!(read(message).unwrap()["Turn"], 1);
+        assert_eq!(read(message).unwrap()["Name"], "}{ \" ]");
+    }
+
+    #[test]
+    fn incomplete_or_invalid_message_is_an_error() {
+        assert_eq!(read("").unwrap_err().kind(), io::ErrorKind::UnexpectedEof);
+        assert_eq!(read("{\"Turn\": [1,\n2\n").unwrap_err().kind(), io::ErrorKind::UnexpectedEof);
+        assert_eq!(read("{\"Turn\": 1,}\n").unwrap_err().kind(), io::ErrorKind::InvalidData);
+        assert_eq!(read("turn-start\n{}\n").unwrap_err().kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn messages_round_trip() {
+        let board_state = BoardState::new().apply_move(&BoardMove { l_piece: [[0, 2], [0, 3], [1, 2], [2, 2]], neutral_pieces: [[1, 0], [3, 3]] }).with_turn(3);
+        let match_config = MatchConfig { player: Some(1), initial_board: Some(BoardState::new()), max_turns: Some(40), turn_timeout: Some(Duration::from_millis(1500)), ..MatchConfig::default() };
+        let messages = [Message::GameInit(match_config), Message::GameStart, Message::Sleep, Message::TurnInit(board_state), Message::TurnStart, Message::Throw];
+
+        let input: String = messages.iter().map(|message| format!("{}\n\n", message)).collect();
+        let mut reader = input.as_bytes();
+
+        for message in messages {
+            assert_eq!(Message::read(&mut reader).unwrap(), Some(message));
+        }
+        assert_eq!(Message::read(&mut reader).unwrap(), None);
+    }
+
+    #[test]
+    fn invalid_messages_are_errors() {
+        let read = |input: &str| Message::read(&mut input.as_bytes());
+
+        assert!(matches!(read("turn-begin\n"), Err(ProtocolError::UnknownCommand(line)) if line == "turn-begin"));
+        assert!(matches!(read("turn-init\n{\"Player\": 0}\n"), Err(ProtocolError::InvalidGameState(LoadError::InvalidField(_)))));
+        assert!(matches!(read("turn-init\n"), Err(ProtocolError::Io(err)) if err.kind() == io::ErrorKind::UnexpectedEof));
+
+        let no_turn = format!("game-init\n{}\n", GAME_STATE.replace(r#""Turn":2,"#, ""));
+        assert!(matches!(read(&no_turn), Ok(Some(Message::GameInit(match_config))) if match_config.initial_board.as_ref().is_some_and(|board_state| board_state.turn() == 1)));
+    }
+
+    #[test]
+    fn match_config_from_game_init() {
+        let payload = GAME_STATE.replace(r#""Player":0"#, r#""Player":1,"MaxTurns":60,"TurnTimeoutMs":2000,"Arena":{"Round":3},"Opponent":"StarterBot""#);
+        let match_config = MatchConfig::load(json::parse(&payload).unwrap());
+
+        assert_eq!(match_config.player, Some(1));
+        assert_eq!(match_config.initial_board, Some(BoardState::new().with_turn(2)));
+        assert_eq!(match_config.max_turns, Some(60));
+        assert_eq!(match_config.turn_timeout, Some(Duration::from_secs(2)));
+        assert_eq!(match_config.extra, json::object! { "Arena": { "Round": 3 }, "Opponent": "StarterBot" });
+
+        // unknown fields are passed on
+        assert_eq!(match_config.to_json(), json::parse(&payload).unwrap());
+        assert_eq!(MatchConfig::load(json::parse(GAME_STATE).unwrap()), MatchConfig { player: Some(0), initial_board: Some(BoardState::new().with_turn(2)), ..MatchConfig::default() });
+    }
+
+    #[test]
+    fn game_init_keeps_what_it_cannot_read() {
+        let payload = json::parse(r#"{"GameState":{"Board":3},"Turn":4,"Player":2,"MaxTurns":"many","TurnTimeoutMs":-5}"#).unwrap();
+        let match_config = MatchConfig::load(payload.clone());
+
+        assert_eq!(match_config, MatchConfig { extra: payload.clone(), ..MatchConfig::default() });
+        assert_eq!(match_config.to_json(), payload);
+        assert_eq!(MatchConfig::load(json::object! {}), MatchConfig::default());
+
+        let read = |input: &str| Message::read(&mut input.as_bytes());
+        let no_game_state = "game-init\n{\"Player\":1,\"MaxTurns\":\"many\"}\n";
+        assert!(matches!(read(no_game_state), Ok(Some(Message::GameInit(match_config))) if match_config.player == Some(1) && match_config.initial_board.is_none()));
+    }
+
+    #[test]
+    fn game_init_accepts_any_json() {
+        for payload in ["[1, 2]", "\"ready\"", "42", "null", "{}"] {
+            let input = format!("game-init\n{}\ngame-start\n", payload);
+            let mut reader = input.as_bytes();
+
+            let Ok(Some(Message::GameInit(match_config))) = Message::read(&mut reader) else { panic!("rejected {}", payload) };
+            assert_eq!(match_config.to_json(), json::parse(payload).unwrap());
+            assert_eq!(Message::read(&mut reader).unwrap(), Some(Message::GameStart));
+        }
+    }
+
+    #[test]
+    fn commands_round_trip() {
+        let board_move = BoardMove { l_piece: [[0, 2], [0, 3], [1, 2], [2, 2]], neutral_pieces: [[1, 0], [3, 3]] };
+
+        for command in [Command::BotStart, Command::PlacePieces(board_move), Command::TurnEnd] {
+            assert_eq!(Command::parse(&command.to_string()), Some(command));
+        }
+        assert_eq!(Command::parse("turn-ended"), None);
+        assert_eq!(Command::parse(r#"{"PlayerLPieceCoordinates":[]}"#), None);
+    }
+}
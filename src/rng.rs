@@ -0,0 +1,77 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Small, seedable pseudo-random number generator (xorshift64*), good enough for playing games.
+#[derive(Clone, Debug)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Rng {
+        // xorshift gets stuck on 0, and similar seeds should give different sequences
+        let mut state = seed.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        state = (state ^ (state >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        state = (state ^ (state >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        Rng { state: (state ^ (state >> 31)).max(1) }
+    }
+
+    /// Seeded from the clock, for when reproducibility doesn't matter.
+    pub fn from_time() -> Rng {
+        Rng::new(SystemTime::now().duration_since(UNIX_EPOCH).map(|duration| duration.as_nanos() as u64).unwrap_or(0))
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    /// Uniformly distributed in `0..bound`; `bound` must not be 0.
+    pub fn below(&mut self, bound: usize) -> usize {
+        (((self.next_u64() >> 32) * bound as u64) >> 32) as usize
+    }
+
+    /// Uniformly distributed in `0.0..1.0`.
+    pub fn next_f32(&mut self) -> f32 {
+        (self.next_u64() >> 40) as f32 / (1u64 << 24) as f32
+    }
+
+    pub fn choose<'a, T>(&mut self, items: &'a [T]) -> Option<&'a T> {
+        if items.is_empty() {
+            None
+        } else {
+            Some(&items[self.below(items.len())])
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn same_seed_same_sequence() {
+        let mut rng = Rng::new(42);
+        let mut other_rng = Rng::new(42);
+        let mut different_rng = Rng::new(43);
+
+        let sequence: Vec<u64> = (0..10).map(|_| rng.next_u64()).collect();
+
+        assert_eq!(sequence, (0..10).map(|_| other_rng.next_u64()).collect::<Vec<_>>());
+        assert_ne!(sequence, (0..10).map(|_| different_rng.next_u64()).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn below_covers_range() {
+        let mut rng = Rng::new(0);
+        let mut counts = [0; 5];
+
+        for _ in 0..5000 {
+            counts[rng.below(5)] += 1;
+        }
+
+        assert!(counts.iter().all(|count| (800..1200).contains(count)), "{:?}", counts);
+        assert!((0..100).map(|_| rng.next_f32()).all(|value| (0.0..1.0).contains(&value)));
+    }
+}
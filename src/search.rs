@@ -0,0 +1,653 @@
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::board_state::{BoardMove, BoardState, CompactMove, Evaluation, WIN_SCORE};
+use crate::evaluation::EvaluationWeights;
+use crate::tablebase::{Outcome, Tablebase};
+use crate::transposition_table::{Bound, TranspositionEntry, TranspositionTable};
+
+/// Iterative deepening stops here, or earlier when a forced win or loss is found.
+pub const MAX_DEPTH: u32 = 64;
+
+/// How many nodes to visit between looking at the clock.
+const NODES_PER_CLOCK_CHECK: u64 = 256;
+
+/// Number of transposition table entries of `Search::new`.
+pub const TRANSPOSITION_TABLE_CAPACITY: usize = 1 << 16;
+
+/// Scores beyond this are wins or losses, stored in the table relative to the position instead of the root.
+const DECIDED_SCORE: f32 = WIN_SCORE - MAX_DEPTH as f32;
+
+fn score_to_table(score: f32, ply: u32) -> f32 {
+    if score >= DECIDED_SCORE {
+        score + ply as f32
+    } else if score <= -DECIDED_SCORE {
+        score - ply as f32
+    } else {
+        score
+    }
+}
+
+/// Score of a won or lost tablebase outcome, `ply` plies from the root. `None` for a draw, and for a trap that
+/// would come too late: with `remaining_turns` left, the scores decide first.
+fn decided_score(outcome: Outcome, ply: u32, remaining_turns: Option<u32>) -> Option<f32> {
+    match outcome {
+        Outcome::Win(distance) | Outcome::Loss(distance) if remaining_turns.is_some_and(|remaining_turns| distance as u32 >= remaining_turns) => None,
+        Outcome::Win(distance) => Some(WIN_SCORE - (ply + distance as u32) as f32),
+        Outcome::Loss(distance) => Some(-(WIN_SCORE - (ply + distance as u32) as f32)),
+        Outcome::Draw => None,
+    }
+}
+
+fn score_from_table(score: f32, ply: u32) -> f32 {
+    if score >= DECIDED_SCORE {
+        score - ply as f32
+    } else if score <= -DECIDED_SCORE {
+        score + ply as f32
+    } else {
+        score
+    }
+}
+
+/// The order in which the search tries moves; the better the first moves, the more alpha-beta prunes.
+#[derive(Clone, Copy, Debug, Default)]
+pub enum MoveOrdering {
+    /// Keep the order of `calculate_legal_moves`.
+    #[default]
+    Generated,
+    /// Try moves whose L-piece occupies a corner (and therefore scores) first.
+    CornersFirst,
+    /// Try moves that leave the opponent with the fewest legal moves first.
+    MobilityFirst,
+    /// Try moves with the highest key first.
+    Custom(fn(&BoardState, &BoardMove) -> i32),
+}
+
+impl MoveOrdering {
+    pub fn order(&self, board_state: &BoardState, moves: &mut [BoardMove]) {
+        use MoveOrdering::*;
+
+        // sorting is stable, so equal keys keep the generated order
+        match self {
+            Generated => {},
+            CornersFirst => moves.sort_by_key(|board_move| !board_move.occupies_corner()),
+            MobilityFirst => moves.sort_by_cached_key(|board_move| board_state.apply_move(board_move).legal_moves().count()),
+            Custom(key) => moves.sort_by_cached_key(|board_move| -key(board_state, board_move)),
+        }
+    }
+}
+
+/// Node counters of a search, to measure e.g. the gain of pruning and move ordering.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SearchStats {
+    pub nodes: u64,
+    pub leaf_nodes: u64,
+    pub cutoffs: u64,
+    /// Nodes whose score came from the transposition table.
+    pub table_hits: u64,
+    /// Nodes that are won or lost according to the tablebase.
+    pub tablebase_hits: u64,
+    /// Depth of the last completed iteration of `iterative_deepening`.
+    pub completed_depth: u32,
+    /// Whether `iterative_deepening` took the move from the tablebase without searching.
+    pub tablebase_move: bool,
+}
+
+impl SearchStats {
+    /// Adds the node counters of a search on another thread.
+    fn add_nodes(&mut self, other: &SearchStats) {
+        self.nodes += other.nodes;
+        self.leaf_nodes += other.leaf_nodes;
+        self.cutoffs += other.cutoffs;
+        self.table_hits += other.table_hits;
+        self.tablebase_hits += other.tablebase_hits;
+    }
+}
+
+/// Depth-limited alpha-beta search over `BoardState`.
+#[derive(Debug)]
+pub struct Search {
+    pub ordering: MoveOrdering,
+    pub pruning: bool,
+    pub stats: SearchStats,
+    /// Results of earlier (iterations of) searches, kept between calls and shared with the worker threads;
+    /// `None` disables it.
+    pub table: Option<Arc<TranspositionTable>>,
+    /// Perfect play for positions with a forced win or loss; other positions are searched.
+    pub tablebase: Option<Arc<Tablebase>>,
+    /// Iterative deepening stops after this depth.
+    pub max_depth: u32,
+    /// How positions are scored at the search horizon.
+    pub weights: EvaluationWeights,
+    /// Aborts the search when set by another thread, like running out of time.
+    pub stop: Option<Arc<AtomicBool>>,
+    /// Number of threads the root moves are split over. With 1 the search is deterministic; with more, the
+    /// score is the same but equally good moves may be picked in any order.
+    pub threads: usize,
+    deadline: Option<Instant>,
+    aborted: bool,
+}
+
+impl Default for Search {
+    fn default() -> Self {
+        Search::new(MoveOrdering::default())
+    }
+}
+
+impl Search {
+    pub fn new(ordering: MoveOrdering) -> Search {
+        Search { ordering, pruning: true, stats: SearchStats::default(), table: Some(Arc::new(TranspositionTable::new(TRANSPOSITION_TABLE_CAPACITY))), tablebase: None, max_depth: MAX_DEPTH, weights: EvaluationWeights::SCORE_ONLY, stop: None, threads: 1, deadline: None, aborted: false }
+    }
+
+    /// Searches `depth` plies (including our own move) and returns the score and the expected line of play.
+    pub fn search(&mut self, board_state: &BoardState, depth: u32) -> Evaluation {
+        if self.threads > 1 {
+            self.search_parallel(board_state, depth)
+        } else {
+            self.alpha_beta(&mut board_state.clone(), depth, 0, f32::NEG_INFINITY, f32::INFINITY)
+        }
+    }
+
+    /// A single-threaded search with the same settings and table, to search some root moves.
+    fn worker(&self) -> Search {
+        Search {
+            ordering: self.ordering,
+            pruning: self.pruning,
+            stats: SearchStats::default(),
+            table: self.table.clone(),
+            tablebase: self.tablebase.clone(),
+            max_depth: self.max_depth,
+            weights: self.weights,
+            stop: self.stop.clone(),
+            threads: 1,
+            deadline: self.deadline,
+            aborted: false,
+        }
+    }
+
+    /// Splits the root moves over `threads` workers. The best score found so far is shared, so the workers
+    /// prune like a single search would, and so is the transposition table.
+    fn search_parallel(&mut self, board_state: &BoardState, depth: u32) -> Evaluation {
+        let mut root_moves = board_state.calculate_legal_moves();
+
+        if depth == 0 || root_moves.len() < 2 {
+            return self.alpha_beta(&mut board_state.clone(), depth, 0, f32::NEG_INFINITY, f32::INFINITY);
+        }
+
+        self.stats.nodes += 1;
+
+        if self.should_stop() {
+            return Evaluation { score: 0.0, principal_variation: vec![] };
+        }
+
+        let key = board_state.zobrist_hash();
+        let (_, hash_move) = self.probe(board_state, key, depth, 0, f32::NEG_INFINITY, f32::INFINITY);
+        self.order_moves(board_state, &mut root_moves, hash_move);
+
+        let threads = self.threads.min(root_moves.len());
+        let best_score = AtomicU32::new(f32::NEG_INFINITY.to_bits());
+
+        let workers: Vec<(Search, Vec<(usize, Evaluation)>)> = thread::scope(|scope| {
+            let handles: Vec<_> = (0..threads)
+                .map(|thread| {
+                    let mut worker = self.worker();
+                    let mut board_state = board_state.clone();
+                    let root_moves = &root_moves;
+                    let best_score = &best_score;
+
+                    scope.spawn(move || {
+                        let mut results = vec![];
+
+                        for (index, board_move) in root_moves.iter().enumerate().skip(thread).step_by(threads) {
+                            let alpha = if worker.pruning { f32::from_bits(best_score.load(Ordering::Relaxed)) } else { f32::NEG_INFINITY };
+
+                            let undo = board_state.make_move(board_move);
+                            let reply = worker.alpha_beta(&mut board_state, depth - 1, 1, f32::NEG_INFINITY, -alpha);
+                            board_state.unmake_move(undo);
+                            let score = -reply.score;
+
+                            if worker.aborted {
+                                break;
+                            }
+
+                            // a score at or below alpha is only an upper bound
+                            if score > alpha {
+                                let _ = best_score.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |best| (score > f32::from_bits(best)).then_some(score.to_bits()));
+
+                                let mut principal_variation = vec![board_move.clone()];
+                                principal_variation.extend(reply.principal_variation);
+                                results.push((index, Evaluation { score, principal_variation }));
+                            }
+                        }
+
+                        (worker, results)
+                    })
+                })
+                .collect();
+
+            handles.into_iter().map(|handle| handle.join().unwrap()).collect()
+        });
+
+        // of equal scores, the move that was ordered first wins
+        let mut best: Option<(usize, Evaluation)> = None;
+
+        for (worker, results) in workers {
+            self.stats.add_nodes(&worker.stats);
+            self.aborted |= worker.aborted;
+
+            for (index, evaluation) in results {
+                if best.as_ref().is_none_or(|(best_index, best)| evaluation.score > best.score || (evaluation.score == best.score && index < *best_index)) {
+                    best = Some((index, evaluation));
+                }
+            }
+        }
+
+        if self.aborted {
+            return Evaluation { score: 0.0, principal_variation: vec![] };
+        }
+
+        let (_, best) = best.unwrap();
+
+        if let Some(table) = &self.table {
+            let best_move = CompactMove::from_board_move(board_state, &best.principal_variation[0]);
+            table.store(TranspositionEntry { key, depth, bound: Bound::Exact, score: score_to_table(best.score, 0), best_move });
+        }
+
+        best
+    }
+
+    /// Searches depth 1, 2, 3... until `time_budget` runs out, and returns the result of the last completed
+    /// iteration (or `None` when there are no legal moves). The first iteration always completes, unless `stop` is set.
+    pub fn iterative_deepening(&mut self, board_state: &BoardState, time_budget: Duration) -> Option<Evaluation> {
+        // `Duration::MAX` searches until `max_depth` or `stop`
+        let deadline = Instant::now().checked_add(time_budget);
+        self.stats = SearchStats::default();
+        self.deadline = None;
+        self.aborted = false;
+
+        if let Some((board_move, outcome)) = self.tablebase.as_ref().and_then(|tablebase| tablebase.best_move(board_state)) {
+            if let Some(score) = decided_score(outcome, 0, board_state.remaining_turns()) {
+                self.stats.tablebase_move = true;
+                return Some(Evaluation { score, principal_variation: vec![board_move] });
+            }
+        }
+
+        let mut best: Option<Evaluation> = None;
+
+        for depth in 1..=self.max_depth.max(1) {
+            let evaluation = self.search(board_state, depth);
+
+            if self.aborted {
+                break;
+            }
+
+            self.stats.completed_depth = depth;
+            self.deadline = deadline;
+
+            let decided = evaluation.score.abs() >= DECIDED_SCORE;
+            best = Some(evaluation);
+
+            // searching beyond the end of the game can't change the result
+            let complete = board_state.remaining_turns().is_some_and(|remaining_turns| depth >= remaining_turns);
+
+            if decided || complete || deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                break;
+            }
+        }
+
+        self.deadline = None;
+
+        best.filter(|evaluation| !evaluation.principal_variation.is_empty())
+    }
+
+    fn should_stop(&mut self) -> bool {
+        if !self.aborted && self.stats.nodes.is_multiple_of(NODES_PER_CLOCK_CHECK) {
+            let stopped = self.stop.as_ref().is_some_and(|stop| stop.load(Ordering::Relaxed));
+            self.aborted = stopped || self.deadline.is_some_and(|deadline| Instant::now() >= deadline);
+        }
+        self.aborted
+    }
+
+    fn probe(&mut self, board_state: &BoardState, key: u64, depth: u32, ply: u32, alpha: f32, beta: f32) -> (Option<Evaluation>, Option<BoardMove>) {
+        let Some(entry) = self.table.as_ref().and_then(|table| table.probe(key)) else {
+            return (None, None);
+        };
+
+        let score = score_from_table(entry.score, ply);
+        let best_move = entry.best_move.map(|best_move| best_move.to_board_move(board_state));
+        let usable = entry.depth >= depth && match entry.bound {
+            Bound::Exact => true,
+            Bound::Lower => score >= beta,
+            Bound::Upper => score <= alpha,
+        };
+
+        // the root always searches, so there is a complete principal variation to play
+        if usable && ply > 0 {
+            self.stats.table_hits += 1;
+            let principal_variation = best_move.iter().cloned().collect();
+            (Some(Evaluation { score, principal_variation }), best_move)
+        } else {
+            (None, best_move)
+        }
+    }
+
+    fn order_moves(&self, board_state: &BoardState, moves: &mut [BoardMove], hash_move: Option<BoardMove>) {
+        self.ordering.order(board_state, moves);
+
+        // the best move of an earlier search of this position goes first
+        if let Some(index) = hash_move.and_then(|hash_move| moves.iter().position(|board_move| *board_move == hash_move)) {
+            moves[..=index].rotate_right(1);
+        }
+    }
+
+    /// Searches `board_state` in place: every move is made and taken back again.
+    fn alpha_beta(&mut self, board_state: &mut BoardState, depth: u32, ply: u32, mut alpha: f32, beta: f32) -> Evaluation {
+        self.stats.nodes += 1;
+
+        if self.should_stop() {
+            // the result of an aborted iteration is thrown away
+            return Evaluation { score: 0.0, principal_variation: vec![] };
+        }
+
+        // the game is over at the turn limit, and decided on the scores; the root still gets a move
+        if ply > 0 && board_state.remaining_turns() == Some(0) {
+            self.stats.leaf_nodes += 1;
+            return Evaluation { score: board_state.score_difference(), principal_variation: vec![] };
+        }
+
+        if ply > 0 {
+            if let Some(score) = self.tablebase.as_ref().and_then(|tablebase| tablebase.probe(board_state)).and_then(|outcome| decided_score(outcome, ply, board_state.remaining_turns())) {
+                self.stats.leaf_nodes += 1;
+                self.stats.tablebase_hits += 1;
+                return Evaluation { score, principal_variation: vec![] };
+            }
+        }
+
+        let mut legal_moves = board_state.calculate_legal_moves();
+
+        if legal_moves.is_empty() {
+            self.stats.leaf_nodes += 1;
+            return Evaluation { score: -(WIN_SCORE - ply as f32), principal_variation: vec![] };
+        }
+
+        if depth == 0 {
+            self.stats.leaf_nodes += 1;
+            return Evaluation { score: self.weights.evaluate(board_state), principal_variation: vec![] };
+        }
+
+        let key = board_state.zobrist_hash();
+        let (table_evaluation, hash_move) = self.probe(board_state, key, depth, ply, alpha, beta);
+
+        if let Some(evaluation) = table_evaluation {
+            return evaluation;
+        }
+
+        self.order_moves(board_state, &mut legal_moves, hash_move);
+
+        let original_alpha = alpha;
+        let mut best = Evaluation { score: f32::NEG_INFINITY, principal_variation: vec![] };
+
+        for board_move in legal_moves {
+            let undo = board_state.make_move(&board_move);
+            let reply = self.alpha_beta(board_state, depth - 1, ply + 1, -beta, -alpha);
+            board_state.unmake_move(undo);
+            let score = -reply.score;
+
+            if self.aborted {
+                break;
+            }
+
+            if score > best.score {
+                let mut principal_variation = vec![board_move];
+                principal_variation.extend(reply.principal_variation);
+                best = Evaluation { score, principal_variation };
+            }
+
+            if score > alpha {
+                alpha = score;
+            }
+
+            if self.pruning && alpha >= beta {
+                self.stats.cutoffs += 1;
+                break;
+            }
+        }
+
+        if !self.aborted {
+            if let Some(table) = &self.table {
+                let bound = if best.score <= original_alpha {
+                    Bound::Upper
+                } else if best.score >= beta {
+                    Bound::Lower
+                } else {
+                    Bound::Exact
+                };
+                let best_move = best.principal_variation.first().and_then(|best_move| CompactMove::from_board_move(board_state, best_move));
+                table.store(TranspositionEntry { key, depth, bound, score: score_to_table(best.score, ply), best_move });
+            }
+        }
+
+        best
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board_state::trapping_position;
+
+    fn test_positions() -> Vec<BoardState> {
+        let board_state = BoardState::new();
+        let next_board_state = board_state.apply_move(&BoardMove{ l_piece: [[0,2],[1,2],[2,2],[0,3]], neutral_pieces: [[0,0],[3,3]]});
+        vec![board_state, next_board_state]
+    }
+
+    #[test]
+    fn alpha_beta_agrees_with_full_search() {
+        for board_state in test_positions() {
+            let expected = Search { pruning: false, table: None, ..Search::new(MoveOrdering::Generated) }.search(&board_state, 2);
+
+            for ordering in [MoveOrdering::Generated, MoveOrdering::CornersFirst, MoveOrdering::MobilityFirst] {
+                let evaluation = Search::new(ordering).search(&board_state, 2);
+
+                assert_eq!(evaluation.score, expected.score);
+                assert_eq!(evaluation.principal_variation.len(), 2);
+            }
+        }
+    }
+
+    #[test]
+    fn pruning_visits_fewer_nodes() {
+        for board_state in test_positions() {
+            let mut full_search = Search { pruning: false, table: None, ..Search::default() };
+            let full_evaluation = full_search.search(&board_state, 2);
+
+            let mut search = Search { table: None, ..Search::new(MoveOrdering::CornersFirst) };
+            let evaluation = search.search(&board_state, 2);
+
+            assert_eq!(evaluation.score, full_evaluation.score);
+            assert_eq!(full_search.stats.cutoffs, 0);
+            assert!(search.stats.cutoffs > 0);
+            assert!(search.stats.nodes < full_search.stats.nodes);
+        }
+    }
+
+    #[test]
+    fn transposition_table_keeps_score_and_saves_nodes() {
+        for board_state in test_positions() {
+            let mut plain_search = Search { table: None, ..Search::default() };
+            let expected = plain_search.search(&board_state, 3);
+
+            let mut search = Search::default();
+            search.search(&board_state, 2);
+            search.stats = SearchStats::default();
+            let evaluation = search.search(&board_state, 3);
+
+            assert_eq!(evaluation.score, expected.score);
+            assert!(search.stats.table_hits > 0);
+            assert!(search.stats.nodes < plain_search.stats.nodes);
+        }
+    }
+
+    #[test]
+    fn decided_scores_are_stored_relative_to_position() {
+        assert_eq!(score_from_table(score_to_table(WIN_SCORE - 5.0, 3), 1), WIN_SCORE - 3.0);
+        assert_eq!(score_from_table(score_to_table(-WIN_SCORE + 4.0, 4), 2), -WIN_SCORE + 2.0);
+        assert_eq!(score_from_table(score_to_table(3.0, 4), 2), 3.0);
+    }
+
+    #[test]
+    fn tablebase_decides_search() {
+        let tablebase = Arc::new(Tablebase::generate());
+        let board_state = trapping_position();
+
+        let mut search = Search { tablebase: Some(tablebase.clone()), ..Search::default() };
+        let evaluation = search.iterative_deepening(&board_state, Duration::from_secs(10)).unwrap();
+
+        assert_eq!(evaluation.score, WIN_SCORE - 1.0);
+        assert_eq!(search.stats.nodes, 0);
+        assert!(search.stats.tablebase_move);
+
+        // the tablebase knows the opening position is a draw, and every reply we consider keeps it that way
+        let mut search = Search { tablebase: Some(tablebase.clone()), ..Search::default() };
+        let evaluation = search.search(&BoardState::new(), 2);
+
+        assert!(evaluation.score.abs() < DECIDED_SCORE);
+        assert_eq!(tablebase.probe(&BoardState::new().apply_move(&evaluation.principal_variation[0])), Some(Outcome::Draw));
+    }
+
+    #[test]
+    fn iterative_deepening_completes_first_iteration() {
+        let mut search = Search::default();
+
+        let evaluation = search.iterative_deepening(&BoardState::new(), Duration::ZERO).unwrap();
+
+        assert_eq!(search.stats.completed_depth, 1);
+        assert_eq!(evaluation, search.search(&BoardState::new(), 1));
+    }
+
+    #[test]
+    fn iterative_deepening_respects_time_budget() {
+        let mut search = Search::new(MoveOrdering::CornersFirst);
+        let start = Instant::now();
+
+        let evaluation = search.iterative_deepening(&BoardState::new(), Duration::from_millis(200));
+
+        // the depth shows the search stopped early; the clock only guards against hangs
+        assert!(evaluation.is_some());
+        assert!(search.stats.completed_depth >= 1);
+        assert!(search.stats.completed_depth < MAX_DEPTH);
+        assert!(start.elapsed() < Duration::from_secs(10));
+    }
+
+    #[test]
+    fn iterative_deepening_stops_at_forced_win() {
+        let board_state = BoardState::load(json::parse(r#"{
+            "GameState": {
+              "Board": { "Board": [[0, 0, 4, 0], [2, 2, 2, 0], [1, 0, 2, 0], [1, 1, 1, 4]] },
+              "ScorePlayer0": 0,
+              "ScorePlayer1": 0
+            },
+            "Turn": 7,
+            "Player": 1
+          }"#).unwrap()).unwrap();
+        let mut search = Search::default();
+
+        let evaluation = search.iterative_deepening(&board_state, Duration::from_secs(10)).unwrap();
+
+        assert_eq!(evaluation.score, WIN_SCORE - 1.0);
+        assert_eq!(search.stats.completed_depth, 1);
+    }
+
+    #[test]
+    fn parallel_search_agrees_with_single_thread() {
+        for board_state in test_positions() {
+            let expected = Search::new(MoveOrdering::CornersFirst).search(&board_state, 3);
+
+            for threads in [2, 3, 8] {
+                let mut search = Search { threads, ..Search::new(MoveOrdering::CornersFirst) };
+                let evaluation = search.search(&board_state, 3);
+
+                assert_eq!(evaluation.score, expected.score, "{} threads", threads);
+                assert_eq!(evaluation.principal_variation.len(), 3);
+
+                // the chosen move is as good as the score says
+                let reply = Search::default().search(&board_state.apply_move(&evaluation.principal_variation[0]), 2);
+                assert_eq!(-reply.score, evaluation.score);
+
+                assert!(search.stats.nodes > 0);
+                assert!(search.table.as_ref().unwrap().probe(board_state.zobrist_hash()).is_some());
+            }
+        }
+    }
+
+    #[test]
+    fn single_thread_search_is_deterministic() {
+        let search = || {
+            let mut search = Search::new(MoveOrdering::MobilityFirst);
+            let evaluation = search.search(&BoardState::new(), 3);
+            (evaluation, search.stats)
+        };
+
+        assert_eq!(search(), search());
+    }
+
+    #[test]
+    fn parallel_search_stops_when_asked() {
+        let stop = Arc::new(AtomicBool::new(false));
+        let mut search = Search { threads: 4, stop: Some(stop.clone()), ..Search::default() };
+
+        // without a time budget, only the flag ends the search
+        thread::scope(|scope| {
+            scope.spawn(|| {
+                thread::sleep(Duration::from_millis(50));
+                stop.store(true, Ordering::Relaxed);
+            });
+            search.iterative_deepening(&BoardState::new(), Duration::MAX);
+        });
+
+        assert!(search.aborted);
+        assert!(search.stats.completed_depth < MAX_DEPTH);
+    }
+
+    #[test]
+    fn turn_limit_ends_the_search() {
+        let board_state = trapping_position().with_turn(7);
+        let mut search = Search::default();
+
+        // with a turn left after ours, trapping the opponent wins
+        let evaluation = search.iterative_deepening(&board_state.clone().with_max_turns(Some(8)), Duration::from_secs(10)).unwrap();
+        assert_eq!(evaluation.score, WIN_SCORE - 1.0);
+
+        // in the last turn only the final scores count
+        let last_turn = board_state.with_max_turns(Some(7));
+        let best_final_score = last_turn.legal_moves().map(|board_move| -last_turn.apply_move(&board_move).score_difference()).fold(f32::NEG_INFINITY, f32::max);
+        let evaluation = search.iterative_deepening(&last_turn, Duration::from_secs(10)).unwrap();
+
+        assert_eq!(evaluation.score, best_final_score);
+        assert_eq!(evaluation.principal_variation.len(), 1);
+        assert_eq!(search.stats.completed_depth, 1);
+    }
+
+    #[test]
+    fn tablebase_outcomes_beyond_turn_limit_are_not_decided() {
+        assert_eq!(decided_score(Outcome::Win(3), 1, None), Some(WIN_SCORE - 4.0));
+        assert_eq!(decided_score(Outcome::Win(3), 1, Some(4)), Some(WIN_SCORE - 4.0));
+        assert_eq!(decided_score(Outcome::Win(3), 1, Some(3)), None);
+        assert_eq!(decided_score(Outcome::Loss(2), 0, Some(2)), None);
+        assert_eq!(decided_score(Outcome::Draw, 0, None), None);
+    }
+
+    #[test]
+    fn custom_ordering_is_applied() {
+        let board_state = BoardState::new();
+        let mut moves = board_state.calculate_legal_moves();
+
+        MoveOrdering::Custom(|_, board_move| board_move.neutral_pieces[0][1]).order(&board_state, &mut moves);
+
+        assert!(moves.windows(2).all(|pair| pair[0].neutral_pieces[0][1] >= pair[1].neutral_pieces[0][1]));
+        assert!(moves[0].neutral_pieces[0][1] > 0);
+    }
+}
@@ -0,0 +1,388 @@
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use crate::board_state::{BoardMove, BoardState};
+use crate::evaluation::EvaluationWeights;
+use crate::mcts::{Mcts, MctsBudget, MctsConfig};
+use crate::rng::Rng;
+use crate::search::{MoveOrdering, Search, MAX_DEPTH};
+use crate::tablebase::Tablebase;
+
+/// How much a strategy may think about one move. Strategies use the limits that apply to them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SearchLimits {
+    pub time: Duration,
+    /// Maximum search depth in plies.
+    pub depth: Option<u32>,
+    /// Maximum number of MCTS iterations; overrides `time`.
+    pub iterations: Option<u32>,
+    /// Number of threads for the minimax search; 1 gives reproducible results.
+    pub threads: usize,
+}
+
+impl Default for SearchLimits {
+    fn default() -> Self {
+        SearchLimits { time: Duration::from_millis(500), depth: None, iterations: None, threads: 1 }
+    }
+}
+
+/// A way of picking moves; `None` means there are no legal moves.
+pub trait Strategy: Send {
+    fn choose_move(&mut self, board_state: &BoardState, limits: &SearchLimits) -> Option<BoardMove>;
+
+    /// Diagnostics about the last `choose_move`, if the strategy has any.
+    fn report(&self) -> Option<String> {
+        None
+    }
+
+    /// Thinks about `board_state`, the position after our move, until `stop` is set, so the next `choose_move`
+    /// (after the opponent's reply) gets further. Strategies that can't use the time return at once.
+    fn ponder(&mut self, _board_state: &BoardState, _stop: &Arc<AtomicBool>) {}
+}
+
+/// Picks a legal move uniformly at random.
+#[derive(Clone, Debug)]
+pub struct RandomStrategy {
+    rng: Rng,
+}
+
+impl RandomStrategy {
+    pub fn new(rng: Rng) -> RandomStrategy {
+        RandomStrategy { rng }
+    }
+}
+
+impl Strategy for RandomStrategy {
+    fn choose_move(&mut self, board_state: &BoardState, _limits: &SearchLimits) -> Option<BoardMove> {
+        self.rng.choose(&board_state.calculate_legal_moves()).cloned()
+    }
+}
+
+/// Traps the opponent if possible, and otherwise scores if possible, without looking further ahead.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct GreedyStrategy;
+
+impl Strategy for GreedyStrategy {
+    fn choose_move(&mut self, board_state: &BoardState, _limits: &SearchLimits) -> Option<BoardMove> {
+        let mut search = Search::default();
+        search.table = None;
+        search.search(board_state, 1).principal_variation.into_iter().next()
+    }
+}
+
+/// Iterative deepening alpha-beta search.
+#[derive(Debug)]
+pub struct MinimaxStrategy {
+    pub search: Search,
+}
+
+impl MinimaxStrategy {
+    pub fn new(tablebase: Option<Arc<Tablebase>>, weights: EvaluationWeights) -> MinimaxStrategy {
+        let mut search = Search::new(MoveOrdering::CornersFirst);
+        search.tablebase = tablebase;
+        search.weights = weights;
+        MinimaxStrategy { search }
+    }
+}
+
+impl Strategy for MinimaxStrategy {
+    fn choose_move(&mut self, board_state: &BoardState, limits: &SearchLimits) -> Option<BoardMove> {
+        self.search.max_depth = limits.depth.unwrap_or(MAX_DEPTH);
+        self.search.threads = limits.threads.max(1);
+        self.search.iterative_deepening(board_state, limits.time).map(|evaluation| evaluation.principal_variation[0].clone())
+    }
+
+    fn report(&self) -> Option<String> {
+        let stats = self.search.stats;
+        if stats.tablebase_move {
+            return Some("the tablebase chose the move".to_string());
+        }
+        Some(format!("depth {} nodes {} cutoffs {} table hits {} tablebase hits {}", stats.completed_depth, stats.nodes, stats.cutoffs, stats.table_hits, stats.tablebase_hits))
+    }
+
+    /// Searches the opponent's position: the transposition table then holds results for its likely replies.
+    fn ponder(&mut self, board_state: &BoardState, stop: &Arc<AtomicBool>) {
+        let stats = self.search.stats;
+        self.search.stop = Some(stop.clone());
+        self.search.iterative_deepening(board_state, Duration::MAX);
+        self.search.stop = None;
+        self.search.stats = stats;
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct MctsStrategy {
+    pub mcts: Mcts,
+}
+
+impl MctsStrategy {
+    pub fn new(config: MctsConfig, seed: u64) -> MctsStrategy {
+        MctsStrategy { mcts: Mcts::new(config, seed) }
+    }
+}
+
+impl Strategy for MctsStrategy {
+    fn choose_move(&mut self, board_state: &BoardState, limits: &SearchLimits) -> Option<BoardMove> {
+        self.mcts.config.budget = match limits.iterations {
+            Some(iterations) => MctsBudget::Iterations(iterations),
+            None => MctsBudget::Time(limits.time),
+        };
+        self.mcts.search(board_state)
+    }
+
+    fn report(&self) -> Option<String> {
+        Some(format!("iterations {} tree size {} ({} KiB)", self.mcts.iterations(), self.mcts.tree_size(), self.mcts.tree_memory() >> 10))
+    }
+
+    fn ponder(&mut self, board_state: &BoardState, stop: &Arc<AtomicBool>) {
+        self.mcts.ponder(board_state, stop);
+    }
+}
+
+/// Runs another strategy's `ponder` on a background thread while the opponent is thinking, and takes the
+/// strategy back for our next move.
+pub struct PonderingStrategy {
+    /// `None` while the strategy is on the pondering thread.
+    strategy: Option<Box<dyn Strategy>>,
+    pondering: Option<Pondering>,
+}
+
+/// A pondering thread, which hands the strategy back when it's done.
+struct Pondering {
+    stop: Arc<AtomicBool>,
+    thread: JoinHandle<Box<dyn Strategy>>,
+}
+
+impl PonderingStrategy {
+    pub fn new(strategy: Box<dyn Strategy>) -> PonderingStrategy {
+        PonderingStrategy { strategy: Some(strategy), pondering: None }
+    }
+
+    pub fn is_pondering(&self) -> bool {
+        self.pondering.is_some()
+    }
+
+    /// Starts pondering `board_state`, the position after our move.
+    pub fn start_pondering(&mut self, board_state: &BoardState) {
+        self.stop_pondering();
+
+        let mut strategy = self.strategy.take().unwrap();
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread_stop = stop.clone();
+        let board_state = board_state.clone();
+
+        let thread = thread::spawn(move || {
+            strategy.ponder(&board_state, &thread_stop);
+            strategy
+        });
+        self.pondering = Some(Pondering { stop, thread });
+    }
+
+    /// Stops pondering, and waits for the strategy to come back.
+    pub fn stop_pondering(&mut self) {
+        if let Some(Pondering { stop, thread }) = self.pondering.take() {
+            stop.store(true, Ordering::Relaxed);
+            self.strategy = Some(thread.join().unwrap_or_else(|panic| std::panic::resume_unwind(panic)));
+        }
+    }
+}
+
+impl Strategy for PonderingStrategy {
+    fn choose_move(&mut self, board_state: &BoardState, limits: &SearchLimits) -> Option<BoardMove> {
+        self.stop_pondering();
+        self.strategy.as_mut().unwrap().choose_move(board_state, limits)
+    }
+
+    fn report(&self) -> Option<String> {
+        self.strategy.as_ref().and_then(|strategy| strategy.report())
+    }
+
+    fn ponder(&mut self, board_state: &BoardState, stop: &Arc<AtomicBool>) {
+        self.stop_pondering();
+        self.strategy.as_mut().unwrap().ponder(board_state, stop);
+    }
+}
+
+impl Drop for PonderingStrategy {
+    fn drop(&mut self) {
+        self.stop_pondering();
+    }
+}
+
+/// Perfect play as far as trapping goes, ignoring the scores.
+#[derive(Clone, Debug)]
+pub struct TablebaseStrategy {
+    pub tablebase: Arc<Tablebase>,
+}
+
+impl Strategy for TablebaseStrategy {
+    fn choose_move(&mut self, board_state: &BoardState, _limits: &SearchLimits) -> Option<BoardMove> {
+        self.tablebase.best_move(board_state).map(|(board_move, _)| board_move)
+    }
+}
+
+/// The strategies that can be selected by name.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StrategyKind {
+    Random,
+    Greedy,
+    Minimax,
+    Mcts,
+    Tablebase,
+}
+
+impl FromStr for StrategyKind {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<StrategyKind, String> {
+        use StrategyKind::*;
+        match name {
+            "random" => Ok(Random),
+            "greedy" => Ok(Greedy),
+            "minimax" => Ok(Minimax),
+            "mcts" => Ok(Mcts),
+            "tablebase" => Ok(Tablebase),
+            _ => Err(format!("unknown strategy \"{}\", expected random, greedy, minimax, mcts or tablebase", name)),
+        }
+    }
+}
+
+impl StrategyKind {
+    pub fn uses_tablebase(&self) -> bool {
+        matches!(self, StrategyKind::Minimax | StrategyKind::Tablebase)
+    }
+
+    /// Creates the strategy; the tablebase is generated if it's needed but not given. Strategies that play
+    /// randomly use `seed`, so the same seed gives the same game against the same opponent.
+    pub fn create(&self, tablebase: Option<Arc<Tablebase>>, seed: u64) -> Box<dyn Strategy> {
+        self.create_with_weights(tablebase, seed, EvaluationWeights::default())
+    }
+
+    /// Like `create`, with the evaluation weights for strategies that evaluate positions.
+    pub fn create_with_weights(&self, tablebase: Option<Arc<Tablebase>>, seed: u64, weights: EvaluationWeights) -> Box<dyn Strategy> {
+        match self {
+            StrategyKind::Random => Box::new(RandomStrategy::new(Rng::new(seed))),
+            StrategyKind::Greedy => Box::new(GreedyStrategy),
+            StrategyKind::Minimax => Box::new(MinimaxStrategy::new(tablebase, weights)),
+            StrategyKind::Mcts => Box::new(MctsStrategy::new(MctsConfig::default(), seed)),
+            StrategyKind::Tablebase => Box::new(TablebaseStrategy { tablebase: tablebase.unwrap_or_else(|| Arc::new(Tablebase::generate())) }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board_state::{trapping_position, TRAPPING_BOARD};
+
+    #[test]
+    fn strategy_kind_from_name() {
+        assert_eq!("mcts".parse(), Ok(StrategyKind::Mcts));
+        assert_eq!("tablebase".parse(), Ok(StrategyKind::Tablebase));
+        assert!("alphazero".parse::<StrategyKind>().is_err());
+    }
+
+    #[test]
+    fn strategies_find_winning_move() {
+        let limits = SearchLimits { time: Duration::from_secs(5), depth: Some(2), iterations: Some(1000), threads: 1 };
+        let tablebase = Arc::new(Tablebase::generate());
+        let board_state = trapping_position();
+
+        for kind in [StrategyKind::Greedy, StrategyKind::Minimax, StrategyKind::Mcts, StrategyKind::Tablebase] {
+            let board_move = kind.create(Some(tablebase.clone()), 0).choose_move(&board_state, &limits).unwrap();
+
+            assert_eq!(board_state.apply_move(&board_move).calculate_legal_moves(), vec![], "{:?}", kind);
+        }
+    }
+
+    #[test]
+    fn minimax_reports_tablebase_moves() {
+        let mut strategy = MinimaxStrategy::new(Some(Tablebase::shared()), EvaluationWeights::default());
+
+        strategy.choose_move(&trapping_position(), &SearchLimits::default()).unwrap();
+        assert_eq!(strategy.report().unwrap(), "the tablebase chose the move");
+
+        strategy.choose_move(&BoardState::new(), &SearchLimits { depth: Some(1), ..SearchLimits::default() }).unwrap();
+        assert!(strategy.report().unwrap().starts_with("depth 1 "));
+    }
+
+    #[test]
+    fn random_strategy_plays_legal_moves() {
+        let mut strategy = RandomStrategy::new(Rng::new(5));
+        let board_state = BoardState::new();
+
+        for _ in 0..20 {
+            let board_move = strategy.choose_move(&board_state, &SearchLimits::default()).unwrap();
+            assert!(board_state.calculate_legal_moves().contains(&board_move));
+        }
+
+        assert_eq!(strategy.choose_move(&BoardState::from_board(TRAPPING_BOARD, 1, (0, 0)), &SearchLimits::default()), None);
+    }
+
+    #[test]
+    fn random_strategy_is_reproducible() {
+        let play_game = |seed| {
+            let mut strategy = StrategyKind::Random.create(None, seed);
+            let mut board_state = BoardState::new();
+            let mut moves = vec![];
+            while let Some(board_move) = strategy.choose_move(&board_state, &SearchLimits::default()) {
+                board_state = board_state.apply_move(&board_move);
+                moves.push(board_move);
+                if moves.len() == 30 {
+                    break;
+                }
+            }
+            moves
+        };
+
+        assert_eq!(play_game(42), play_game(42));
+        assert_ne!(play_game(42), play_game(43));
+    }
+
+    #[test]
+    fn pondering_warms_up_the_search() {
+        let limits = SearchLimits { time: Duration::from_secs(10), depth: Some(3), iterations: None, threads: 1 };
+        let board_state = BoardState::new();
+        let reply = board_state.legal_moves().next().unwrap();
+        let replied = board_state.apply_move(&reply);
+
+        let mut fresh = MinimaxStrategy::new(None, EvaluationWeights::SCORE_ONLY);
+        let board_move = fresh.choose_move(&replied, &limits);
+
+        let mut pondered = MinimaxStrategy::new(None, EvaluationWeights::SCORE_ONLY);
+        pondered.search.max_depth = 4;
+        pondered.ponder(&board_state, &Arc::new(AtomicBool::new(false)));
+
+        assert_eq!(pondered.choose_move(&replied, &limits), board_move);
+        assert!(pondered.search.stats.nodes < fresh.search.stats.nodes);
+    }
+
+    #[test]
+    fn pondering_strategy_stops_for_next_move() {
+        let limits = SearchLimits { time: Duration::from_millis(50), ..SearchLimits::default() };
+        let mut strategy = PonderingStrategy::new(StrategyKind::Minimax.create(None, 0));
+        let mut board_state = BoardState::new();
+
+        for _ in 0..3 {
+            let board_move = strategy.choose_move(&board_state, &limits).unwrap();
+            assert!(!strategy.is_pondering());
+            assert!(board_state.is_legal_move(&board_move));
+
+            board_state = board_state.apply_move(&board_move);
+            strategy.start_pondering(&board_state);
+            assert!(strategy.is_pondering());
+
+            board_state = board_state.apply_move(&board_state.legal_moves().next().unwrap());
+        }
+    }
+
+    #[test]
+    fn greedy_strategy_scores() {
+        let board_move = GreedyStrategy.choose_move(&BoardState::new(), &SearchLimits::default()).unwrap();
+
+        assert!(board_move.occupies_corner());
+    }
+}
@@ -0,0 +1,70 @@
+/// One of the 8 symmetries of the 4x4 board: an optional transposition followed by optional flips.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Symmetry(u8);
+
+const FLIP_X: u8 = 1;
+const FLIP_Y: u8 = 2;
+const TRANSPOSE: u8 = 4;
+
+impl Symmetry {
+    pub const IDENTITY: Symmetry = Symmetry(0);
+
+    pub const ALL: [Symmetry; 8] = [Symmetry(0), Symmetry(1), Symmetry(2), Symmetry(3), Symmetry(4), Symmetry(5), Symmetry(6), Symmetry(7)];
+
+    pub fn apply(&self, [x, y]: [i32; 2]) -> [i32; 2] {
+        let [x, y] = if self.0 & TRANSPOSE != 0 { [y, x] } else { [x, y] };
+        [
+            if self.0 & FLIP_X != 0 { 3 - x } else { x },
+            if self.0 & FLIP_Y != 0 { 3 - y } else { y },
+        ]
+    }
+
+    pub fn apply_to_square(&self, square: usize) -> usize {
+        let [x, y] = self.apply([(square % 4) as i32, (square / 4) as i32]);
+        (y * 4 + x) as usize
+    }
+
+    pub fn inverse(&self) -> Symmetry {
+        if self.0 & TRANSPOSE != 0 {
+            // flipping after transposing is transposing after flipping the other axis
+            Symmetry(TRANSPOSE | (self.0 & FLIP_X) << 1 | (self.0 & FLIP_Y) >> 1)
+        } else {
+            *self
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn symmetries_are_distinct_permutations() {
+        let images: Vec<Vec<usize>> = Symmetry::ALL.iter().map(|symmetry| (0..16).map(|square| symmetry.apply_to_square(square)).collect()).collect();
+
+        for (i, image) in images.iter().enumerate() {
+            let mut sorted = image.clone();
+            sorted.sort();
+            assert_eq!(sorted, (0..16).collect::<Vec<_>>());
+            assert!(images[..i].iter().all(|other| other != image));
+        }
+    }
+
+    #[test]
+    fn inverse_undoes_symmetry() {
+        for symmetry in Symmetry::ALL {
+            for square in 0..16 {
+                assert_eq!(symmetry.inverse().apply_to_square(symmetry.apply_to_square(square)), square);
+            }
+        }
+    }
+
+    #[test]
+    fn corners_map_to_corners() {
+        for symmetry in Symmetry::ALL {
+            for corner in [0, 3, 12, 15] {
+                assert!([0, 3, 12, 15].contains(&symmetry.apply_to_square(corner)));
+            }
+        }
+    }
+}
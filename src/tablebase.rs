@@ -0,0 +1,312 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::{Arc, OnceLock};
+
+use crate::board_state::{BoardMove, BoardState};
+
+/// Game-theoretic value of a position for the player to move, with the number of plies until a player is trapped.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Outcome {
+    Win(u8),
+    Loss(u8),
+    Draw,
+}
+
+impl Outcome {
+    /// The outcome for the player who moved into a position with this outcome.
+    fn for_previous_player(&self) -> Outcome {
+        match *self {
+            Outcome::Win(distance) => Outcome::Loss(distance + 1),
+            Outcome::Loss(distance) => Outcome::Win(distance + 1),
+            Outcome::Draw => Outcome::Draw,
+        }
+    }
+
+    /// Higher is better: quick wins, then draws, then slow losses.
+    fn rank(&self) -> i32 {
+        match *self {
+            Outcome::Win(distance) => 1000 - distance as i32,
+            Outcome::Draw => 0,
+            Outcome::Loss(distance) => -1000 + distance as i32,
+        }
+    }
+
+    fn to_byte(self) -> u8 {
+        match self {
+            Outcome::Draw => 0,
+            Outcome::Win(distance) => distance,
+            Outcome::Loss(distance) => 0x80 | distance,
+        }
+    }
+
+    fn from_byte(byte: u8) -> Outcome {
+        match byte {
+            0 => Outcome::Draw,
+            byte if byte & 0x80 != 0 => Outcome::Loss(byte & 0x7f),
+            byte => Outcome::Win(byte),
+        }
+    }
+}
+
+const MAGIC: &[u8; 4] = b"LTB1";
+
+/// Outcome of every board layout (ignoring scores) under perfect play, solved by retrograde analysis.
+///
+/// Positions are stored from the perspective of the player to move, one per symmetry class.
+#[derive(Clone, Debug)]
+pub struct Tablebase {
+    indices: HashMap<u64, usize>,
+    outcomes: Vec<Outcome>,
+}
+
+/// Key of the position: the bitboards of the canonical board with the player to move as player 1.
+fn position_key(board_state: &BoardState) -> u64 {
+    let mut l_pieces = board_state.l_pieces();
+    if board_state.current_player() == 2 {
+        l_pieces.reverse();
+    }
+    let canonical = BoardState::from_bitboards(l_pieces, board_state.neutral_pieces(), 1, (0, 0)).canonical().0;
+    let [own, other] = canonical.l_pieces();
+    own as u64 | (other as u64) << 16 | (canonical.neutral_pieces() as u64) << 32
+}
+
+fn position_from_key(key: u64) -> BoardState {
+    BoardState::from_bitboards([key as u16, (key >> 16) as u16], (key >> 32) as u16, 1, (0, 0))
+}
+
+/// Keys of all legal positions, in a fixed order, and their indices.
+fn enumerate_positions() -> (Vec<u64>, HashMap<u64, usize>) {
+    let placements = BoardState::l_piece_placements();
+    let mut keys = vec![];
+    let mut indices = HashMap::new();
+
+    for own_placement in &placements {
+        for other_placement in &placements {
+            if own_placement.iter().any(|square| other_placement.contains(square)) {
+                continue;
+            }
+
+            let mut board = [0; 16];
+            for [x, y] in own_placement {
+                board[(y * 4 + x) as usize] = 1;
+            }
+            for [x, y] in other_placement {
+                board[(y * 4 + x) as usize] = 2;
+            }
+
+            let free_squares: Vec<usize> = (0..16).filter(|square| board[*square] == 0).collect();
+            for (i, first) in free_squares.iter().enumerate() {
+                for second in &free_squares[i + 1..] {
+                    let mut board = board;
+                    board[*first] = 4;
+                    board[*second] = 4;
+
+                    let key = position_key(&BoardState::from_board(board, 1, (0, 0)));
+                    indices.entry(key).or_insert_with(|| {
+                        keys.push(key);
+                        keys.len() - 1
+                    });
+                }
+            }
+        }
+    }
+
+    (keys, indices)
+}
+
+impl Tablebase {
+    /// A tablebase generated once per process, for callers that don't bring their own.
+    pub fn shared() -> Arc<Tablebase> {
+        static TABLEBASE: OnceLock<Arc<Tablebase>> = OnceLock::new();
+        TABLEBASE.get_or_init(|| Arc::new(Tablebase::generate())).clone()
+    }
+
+    pub fn generate() -> Tablebase {
+        let (keys, indices) = enumerate_positions();
+
+        let successors: Vec<Vec<usize>> = keys
+            .iter()
+            .map(|key| {
+                let board_state = position_from_key(*key);
+                let mut successors: Vec<usize> = board_state
+                    .legal_moves()
+                    .map(|board_move| indices[&position_key(&board_state.apply_move(&board_move))])
+                    .collect();
+                successors.sort_unstable();
+                successors.dedup();
+                successors
+            })
+            .collect();
+
+        let mut outcomes: Vec<Option<Outcome>> = successors.iter().map(|successors| successors.is_empty().then_some(Outcome::Loss(0))).collect();
+
+        // a win in n needs a successor lost in n - 1, a loss in n needs all successors won (the slowest in n - 1)
+        let mut distance = 1;
+        let mut passes_without_change = 0;
+        while passes_without_change < 2 {
+            let mut changed = false;
+
+            for index in 0..keys.len() {
+                if outcomes[index].is_some() {
+                    continue;
+                }
+
+                let outcome = if distance % 2 == 1 {
+                    successors[index].iter().any(|successor| outcomes[*successor] == Some(Outcome::Loss(distance - 1))).then_some(Outcome::Win(distance))
+                } else {
+                    successors[index].iter().all(|successor| matches!(outcomes[*successor], Some(Outcome::Win(_)))).then_some(Outcome::Loss(distance))
+                };
+
+                if outcome.is_some() {
+                    outcomes[index] = outcome;
+                    changed = true;
+                }
+            }
+
+            passes_without_change = if changed { 0 } else { passes_without_change + 1 };
+            distance += 1;
+        }
+
+        let outcomes = outcomes.into_iter().map(|outcome| outcome.unwrap_or(Outcome::Draw)).collect();
+
+        Tablebase { indices, outcomes }
+    }
+
+    /// Number of positions, one per symmetry class.
+    pub fn len(&self) -> usize {
+        self.outcomes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.outcomes.is_empty()
+    }
+
+    /// Outcome for the player to move, or `None` if the board is not a legal layout.
+    pub fn probe(&self, board_state: &BoardState) -> Option<Outcome> {
+        self.indices.get(&position_key(board_state)).map(|index| self.outcomes[*index])
+    }
+
+    /// The move with the best outcome: the quickest win, a draw, or the slowest loss.
+    pub fn best_move(&self, board_state: &BoardState) -> Option<(BoardMove, Outcome)> {
+        board_state
+            .legal_moves()
+            .filter_map(|board_move| {
+                let outcome = self.probe(&board_state.apply_move(&board_move))?.for_previous_player();
+                Some((board_move, outcome))
+            })
+            .max_by_key(|(_, outcome)| outcome.rank())
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend((self.outcomes.len() as u32).to_le_bytes());
+        bytes.extend(self.outcomes.iter().map(|outcome| outcome.to_byte()));
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> io::Result<Tablebase> {
+        let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message.to_string());
+
+        if bytes.len() < 8 || &bytes[..4] != MAGIC {
+            return Err(invalid("not a tablebase file"));
+        }
+
+        let (keys, indices) = enumerate_positions();
+        let count = u32::from_le_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]) as usize;
+
+        if count != keys.len() || bytes.len() != 8 + count {
+            return Err(invalid("tablebase file does not match the positions of the L game"));
+        }
+
+        let outcomes = bytes[8..].iter().map(|byte| Outcome::from_byte(*byte)).collect();
+
+        Ok(Tablebase { indices, outcomes })
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        fs::write(path, self.to_bytes())
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Tablebase> {
+        Tablebase::from_bytes(&fs::read(path)?)
+    }
+
+    /// Loads the tablebase at `path`, or generates it and saves it there.
+    pub fn load_or_generate(path: impl AsRef<Path>) -> io::Result<Tablebase> {
+        match Tablebase::load(&path) {
+            Ok(tablebase) => Ok(tablebase),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                let tablebase = Tablebase::generate();
+                tablebase.save(&path)?;
+                Ok(tablebase)
+            },
+            Err(err) => Err(err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board_state::{trapping_position, TRAPPING_BOARD};
+
+    fn tablebase() -> Arc<Tablebase> {
+        Tablebase::shared()
+    }
+
+    #[test]
+    fn tablebase_solves_the_l_game() {
+        let tablebase = tablebase();
+        let count = |predicate: fn(&Outcome) -> bool| tablebase.outcomes.iter().filter(|outcome| predicate(outcome)).count();
+
+        assert_eq!(tablebase.len(), 2296);
+        assert_eq!(count(|outcome| *outcome == Outcome::Loss(0)), 15);
+        assert_eq!(count(|outcome| matches!(outcome, Outcome::Loss(_))), 29);
+
+        // the L game is a draw
+        assert_eq!(tablebase.probe(&BoardState::new()), Some(Outcome::Draw));
+    }
+
+    #[test]
+    fn probe_ignores_player_colors_and_symmetry() {
+        let tablebase = tablebase();
+        let trapped = BoardState::from_board(TRAPPING_BOARD, 1, (0, 0));
+        let trapped_player_2 = BoardState::from_board(trapped.board().map(|value| [0, 2, 1, 3, 4][value as usize]), 2, (5, 3));
+
+        assert_eq!(tablebase.probe(&trapped), Some(Outcome::Loss(0)));
+        assert_eq!(tablebase.probe(&trapped_player_2), Some(Outcome::Loss(0)));
+        assert_eq!(tablebase.probe(&trapped_player_2.transformed(crate::symmetry::Symmetry::ALL[5])), Some(Outcome::Loss(0)));
+        assert_eq!(tablebase.probe(&BoardState::from_board([0; 16], 1, (0, 0))), None);
+    }
+
+    #[test]
+    fn best_move_keeps_outcome() {
+        let tablebase = tablebase();
+        let board_state = trapping_position();
+
+        let (board_move, outcome) = tablebase.best_move(&board_state).unwrap();
+
+        assert_eq!(outcome, Outcome::Win(1));
+        assert_eq!(board_state.apply_move(&board_move).calculate_legal_moves(), vec![]);
+
+        for (index, key) in tablebase.indices.keys().enumerate().filter(|(index, _)| index % 16 == 0) {
+            let board_state = position_from_key(*key);
+            let expected = tablebase.probe(&board_state).unwrap();
+
+            assert_eq!(tablebase.best_move(&board_state).map(|(_, outcome)| outcome), (expected != Outcome::Loss(0)).then_some(expected), "position {}", index);
+        }
+    }
+
+    #[test]
+    fn tablebase_round_trips_through_bytes() {
+        let tablebase = tablebase();
+        let bytes = tablebase.to_bytes();
+
+        assert_eq!(bytes.len(), 8 + 2296);
+        assert_eq!(Tablebase::from_bytes(&bytes).unwrap().outcomes, tablebase.outcomes);
+        assert!(Tablebase::from_bytes(&bytes[..100]).is_err());
+        assert!(Tablebase::from_bytes(b"not a tablebase").is_err());
+    }
+}
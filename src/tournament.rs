@@ -0,0 +1,254 @@
+use crate::board_state::BoardState;
+use crate::strategy::{SearchLimits, Strategy};
+
+/// How a game between two strategies ended. Players are 0 and 1, as in the match protocol.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct GameRecord {
+    pub winner: Option<usize>,
+    pub scores: (i32, i32),
+    pub turns: u32,
+    /// Whether the loser got trapped (or played an illegal move), rather than losing on score.
+    pub decisive: bool,
+}
+
+/// Plays a game in-process from the opening position; after `max_turns` the scores decide, which the
+/// strategies are told through the board state.
+pub fn play_game(strategies: [&mut dyn Strategy; 2], limits: [&SearchLimits; 2], max_turns: u32) -> GameRecord {
+    let mut board_state = BoardState::new().with_max_turns(Some(max_turns));
+
+    while board_state.turn() <= max_turns {
+        let turn = board_state.turn();
+        let player = board_state.current_player() as usize - 1;
+
+        match strategies[player].choose_move(&board_state, limits[player]) {
+            Some(board_move) if board_state.is_legal_move(&board_move) => board_state = board_state.apply_move(&board_move),
+            _ => return GameRecord { winner: Some(1 - player), scores: board_state.scores(), turns: turn, decisive: true },
+        }
+    }
+
+    let (score_player_0, score_player_1) = board_state.scores();
+    let winner = match score_player_0.cmp(&score_player_1) {
+        std::cmp::Ordering::Greater => Some(0),
+        std::cmp::Ordering::Less => Some(1),
+        std::cmp::Ordering::Equal => None,
+    };
+
+    GameRecord { winner, scores: board_state.scores(), turns: max_turns, decisive: false }
+}
+
+/// Results of one engine against another, from the first engine's point of view.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MatchStats {
+    pub wins: u32,
+    pub losses: u32,
+    pub draws: u32,
+    /// Sum of our final score minus the opponent's.
+    pub score_differential: i64,
+}
+
+impl MatchStats {
+    /// Adds a game in which we were `player`.
+    pub fn add(&mut self, record: &GameRecord, player: usize) {
+        match record.winner {
+            Some(winner) if winner == player => self.wins += 1,
+            Some(_) => self.losses += 1,
+            None => self.draws += 1,
+        }
+        let (score_player_0, score_player_1) = record.scores;
+        let differential = if player == 0 { score_player_0 - score_player_1 } else { score_player_1 - score_player_0 };
+        self.score_differential += differential as i64;
+    }
+
+    pub fn games(&self) -> u32 {
+        self.wins + self.losses + self.draws
+    }
+
+    /// The stats from the opponent's point of view.
+    pub fn reversed(&self) -> MatchStats {
+        MatchStats { wins: self.losses, losses: self.wins, draws: self.draws, score_differential: -self.score_differential }
+    }
+
+    fn score(&self) -> f64 {
+        (self.wins as f64 + self.draws as f64 / 2.0) / self.games() as f64
+    }
+
+    /// Variance of the result of a single game (1, 0.5 or 0).
+    fn variance(&self) -> f64 {
+        let score = self.score();
+        let games = self.games() as f64;
+        (self.wins as f64 * (1.0 - score).powi(2) + self.draws as f64 * (0.5 - score).powi(2) + self.losses as f64 * score.powi(2)) / games
+    }
+
+    /// Elo difference with a 95% confidence interval, as (estimate, lower bound, upper bound).
+    /// Infinite when all games were won or lost; `None` without games.
+    pub fn elo(&self) -> Option<(f64, f64, f64)> {
+        if self.games() == 0 {
+            return None;
+        }
+
+        let margin = 1.96 * (self.variance() / self.games() as f64).sqrt();
+        let score = self.score();
+
+        Some((score_to_elo(score), score_to_elo(score - margin), score_to_elo(score + margin)))
+    }
+}
+
+impl std::ops::AddAssign for MatchStats {
+    fn add_assign(&mut self, other: MatchStats) {
+        self.wins += other.wins;
+        self.losses += other.losses;
+        self.draws += other.draws;
+        self.score_differential += other.score_differential;
+    }
+}
+
+/// Elo difference corresponding to an expected score.
+pub fn score_to_elo(score: f64) -> f64 {
+    if score <= 0.0 {
+        f64::NEG_INFINITY
+    } else if score >= 1.0 {
+        f64::INFINITY
+    } else {
+        -400.0 * (1.0 / score - 1.0).log10()
+    }
+}
+
+fn elo_to_score(elo: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf(-elo / 400.0))
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SprtDecision {
+    /// The change is at most `elo0` better: stop and reject it.
+    AcceptH0,
+    /// The change is at least `elo1` better: stop and accept it.
+    AcceptH1,
+}
+
+/// Sequential probability ratio test of H0: elo = `elo0` against H1: elo = `elo1`, to stop an A/B test as soon
+/// as the results are conclusive at the given error rates.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Sprt {
+    pub elo0: f64,
+    pub elo1: f64,
+    pub alpha: f64,
+    pub beta: f64,
+}
+
+impl Sprt {
+    pub fn new(elo0: f64, elo1: f64) -> Sprt {
+        Sprt { elo0, elo1, alpha: 0.05, beta: 0.05 }
+    }
+
+    /// Log-likelihood ratio of the results, using the normal approximation of the trinomial model.
+    pub fn llr(&self, stats: &MatchStats) -> f64 {
+        if stats.games() == 0 {
+            return 0.0;
+        }
+
+        // half a game of each result keeps the variance from being 0 after a clean sweep
+        let wins = stats.wins as f64 + 0.5;
+        let losses = stats.losses as f64 + 0.5;
+        let draws = stats.draws as f64 + 0.5;
+        let games = wins + losses + draws;
+
+        let score = (wins + draws / 2.0) / games;
+        let variance = (wins * (1.0 - score).powi(2) + draws * (0.5 - score).powi(2) + losses * score.powi(2)) / games / games;
+
+        let score_0 = elo_to_score(self.elo0);
+        let score_1 = elo_to_score(self.elo1);
+
+        (score_1 - score_0) * (2.0 * score - score_0 - score_1) / (2.0 * variance)
+    }
+
+    /// The (lower, upper) log-likelihood ratio bounds.
+    pub fn bounds(&self) -> (f64, f64) {
+        ((self.beta / (1.0 - self.alpha)).ln(), ((1.0 - self.beta) / self.alpha).ln())
+    }
+
+    pub fn decision(&self, stats: &MatchStats) -> Option<SprtDecision> {
+        let llr = self.llr(stats);
+        let (lower, upper) = self.bounds();
+
+        if llr >= upper {
+            Some(SprtDecision::AcceptH1)
+        } else if llr <= lower {
+            Some(SprtDecision::AcceptH0)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::Rng;
+    use crate::strategy::{GreedyStrategy, RandomStrategy};
+
+    fn stats(wins: u32, losses: u32, draws: u32) -> MatchStats {
+        MatchStats { wins, losses, draws, score_differential: 0 }
+    }
+
+    #[test]
+    fn games_end_by_trapping_or_turn_limit() {
+        let limits = SearchLimits::default();
+
+        for seed in 0..5 {
+            let record = play_game([&mut GreedyStrategy, &mut RandomStrategy::new(Rng::new(seed))], [&limits, &limits], 40);
+
+            assert!(record.turns <= 40);
+            assert!(!record.decisive || record.winner.is_some());
+            assert!(record.decisive || record.turns == 40);
+        }
+
+        // a player without moves loses, even when ahead on score
+        let record = play_game([&mut GreedyStrategy, &mut GreedyStrategy], [&limits, &limits], 40);
+        assert_eq!(record, GameRecord { winner: Some(1), scores: (2, 1), turns: 5, decisive: true });
+    }
+
+    #[test]
+    fn match_stats_from_both_sides() {
+        let mut match_stats = MatchStats::default();
+
+        match_stats.add(&GameRecord { winner: Some(0), scores: (5, 2), turns: 10, decisive: false }, 0);
+        match_stats.add(&GameRecord { winner: Some(0), scores: (5, 2), turns: 10, decisive: false }, 1);
+        match_stats.add(&GameRecord { winner: None, scores: (1, 1), turns: 10, decisive: false }, 1);
+
+        assert_eq!(match_stats, MatchStats { wins: 1, losses: 1, draws: 1, score_differential: 0 });
+        assert_eq!(match_stats.reversed().games(), 3);
+
+        match_stats += MatchStats { wins: 2, losses: 0, draws: 0, score_differential: 7 };
+        assert_eq!(match_stats, MatchStats { wins: 3, losses: 1, draws: 1, score_differential: 7 });
+    }
+
+    #[test]
+    fn elo_of_results() {
+        let (elo, lower, upper) = stats(10, 10, 5).elo().unwrap();
+        assert_eq!(elo, 0.0);
+        assert!(lower < 0.0 && upper > 0.0);
+        assert!((lower + upper).abs() < 1e-9);
+
+        // 75% is about 191 Elo
+        let (elo, lower, upper) = stats(75, 25, 0).elo().unwrap();
+        assert!((elo - 190.85).abs() < 0.01);
+        assert!(lower < elo && elo < upper);
+
+        assert_eq!(stats(3, 0, 0).elo().unwrap().0, f64::INFINITY);
+        assert_eq!(MatchStats::default().elo(), None);
+    }
+
+    #[test]
+    fn sprt_decides_when_conclusive() {
+        let sprt = Sprt::new(0.0, 20.0);
+
+        assert_eq!(sprt.decision(&stats(0, 0, 0)), None);
+        assert_eq!(sprt.decision(&stats(12, 10, 20)), None);
+        assert_eq!(sprt.decision(&stats(700, 400, 900)), Some(SprtDecision::AcceptH1));
+        assert_eq!(sprt.decision(&stats(500, 600, 900)), Some(SprtDecision::AcceptH0));
+
+        // a clean sweep is conclusive too, but not after a single game
+        assert_eq!(sprt.decision(&stats(1, 0, 0)), None);
+        assert_eq!(sprt.decision(&stats(30, 0, 0)), Some(SprtDecision::AcceptH1));
+    }
+}
@@ -0,0 +1,163 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::board_state::CompactMove;
+
+/// How a stored score relates to the true score of the position.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Bound {
+    Exact,
+    /// The search failed high: the true score is at least this.
+    Lower,
+    /// The search failed low: the true score is at most this.
+    Upper,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TranspositionEntry {
+    pub key: u64,
+    pub depth: u32,
+    pub bound: Bound,
+    pub score: f32,
+    pub best_move: Option<CompactMove>,
+}
+
+/// Fixed-size hash table of search results, indexed by the Zobrist hash of the position. It can be shared
+/// between threads: each slot holds the packed entry and the entry XOR its key, so a slot that another thread
+/// is halfway through writing doesn't match the key, and is a miss.
+#[derive(Debug)]
+pub struct TranspositionTable {
+    entries: Vec<[AtomicU64; 2]>,
+}
+
+const OCCUPIED: u64 = 1 << 42;
+const NO_MOVE: u64 = 0xffff;
+
+impl TranspositionEntry {
+    /// Score in bits 0-31, depth in 32-39, bound in 40-41, `OCCUPIED` and the move in 48-63.
+    fn pack(&self) -> u64 {
+        let bound = match self.bound {
+            Bound::Exact => 0,
+            Bound::Lower => 1,
+            Bound::Upper => 2,
+        };
+        let best_move = self.best_move.map_or(NO_MOVE, |best_move| best_move.bits() as u64);
+        self.score.to_bits() as u64 | (self.depth.min(u8::MAX as u32) as u64) << 32 | bound << 40 | OCCUPIED | best_move << 48
+    }
+
+    fn unpack(key: u64, data: u64) -> TranspositionEntry {
+        let bound = match data >> 40 & 3 {
+            0 => Bound::Exact,
+            1 => Bound::Lower,
+            _ => Bound::Upper,
+        };
+        TranspositionEntry { key, depth: (data >> 32 & 0xff) as u32, bound, score: f32::from_bits(data as u32), best_move: CompactMove::from_bits((data >> 48) as u16) }
+    }
+}
+
+impl TranspositionTable {
+    /// Creates a table with room for `capacity` entries, rounded up to a power of two.
+    pub fn new(capacity: usize) -> TranspositionTable {
+        TranspositionTable { entries: (0..capacity.max(1).next_power_of_two()).map(|_| [AtomicU64::new(0), AtomicU64::new(0)]).collect() }
+    }
+
+    fn index(&self, key: u64) -> usize {
+        key as usize & (self.entries.len() - 1)
+    }
+
+    pub fn probe(&self, key: u64) -> Option<TranspositionEntry> {
+        let [check, data] = &self.entries[self.index(key)];
+        let data = data.load(Ordering::Relaxed);
+        let check = check.load(Ordering::Relaxed);
+
+        (data & OCCUPIED != 0 && check ^ data == key).then(|| TranspositionEntry::unpack(key, data))
+    }
+
+    /// Stores `entry`, unless its slot holds a deeper result for the same position.
+    pub fn store(&self, entry: TranspositionEntry) {
+        if matches!(self.probe(entry.key), Some(existing) if existing.depth > entry.depth) {
+            return;
+        }
+
+        let [check, data] = &self.entries[self.index(entry.key)];
+        let packed = entry.pack();
+        check.store(entry.key ^ packed, Ordering::Relaxed);
+        data.store(packed, Ordering::Relaxed);
+    }
+
+    pub fn clear(&self) {
+        for [check, data] in &self.entries {
+            check.store(0, Ordering::Relaxed);
+            data.store(0, Ordering::Relaxed);
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.entries.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(key: u64, depth: u32) -> TranspositionEntry {
+        TranspositionEntry { key, depth, bound: Bound::Exact, score: depth as f32, best_move: None }
+    }
+
+    #[test]
+    fn capacity_is_power_of_two() {
+        assert_eq!(TranspositionTable::new(1000).capacity(), 1024);
+        assert_eq!(TranspositionTable::new(0).capacity(), 1);
+    }
+
+    #[test]
+    fn probe_checks_full_key() {
+        let table = TranspositionTable::new(16);
+        table.store(entry(3, 1));
+
+        assert_eq!(table.probe(3), Some(entry(3, 1)));
+        assert_eq!(table.probe(3 + 16), None);
+    }
+
+    #[test]
+    fn deeper_entry_is_kept_for_same_position() {
+        let table = TranspositionTable::new(16);
+        table.store(entry(3, 4));
+        table.store(entry(3, 2));
+
+        assert_eq!(table.probe(3), Some(entry(3, 4)));
+
+        // a different position in the same slot always replaces it
+        table.store(entry(3 + 16, 1));
+
+        assert_eq!(table.probe(3), None);
+        assert_eq!(table.probe(3 + 16), Some(entry(3 + 16, 1)));
+
+        table.clear();
+        assert_eq!(table.probe(3 + 16), None);
+    }
+
+    #[test]
+    fn entries_round_trip() {
+        let table = TranspositionTable::new(16);
+        let stored = TranspositionEntry { key: 5, depth: 7, bound: Bound::Lower, score: -998.5, best_move: CompactMove::from_bits(0x312) };
+        table.store(stored);
+
+        assert!(stored.best_move.is_some());
+        assert_eq!(table.probe(5), Some(stored));
+    }
+
+    #[test]
+    fn table_is_shared_between_threads() {
+        let table = TranspositionTable::new(1024);
+
+        std::thread::scope(|scope| {
+            for thread in 0..4 {
+                let table = &table;
+                scope.spawn(move || (0..256).for_each(|key| table.store(entry(key * 4 + thread, 1))));
+            }
+        });
+
+        assert!((0..1024).all(|key| table.probe(key) == Some(entry(key, 1))));
+    }
+}
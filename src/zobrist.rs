@@ -0,0 +1,38 @@
+//! Random keys for Zobrist hashing of `BoardState`, generated at compile time.
+
+const fn splitmix64(state: u64) -> u64 {
+    let mut z = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+const fn piece_keys() -> [[u64; 16]; 3] {
+    let mut keys = [[0; 16]; 3];
+    let mut piece = 0;
+    while piece < 3 {
+        let mut square = 0;
+        while square < 16 {
+            keys[piece][square] = splitmix64((piece * 16 + square) as u64);
+            square += 1;
+        }
+        piece += 1;
+    }
+    keys
+}
+
+/// Keys for player 1's L-piece, player 2's L-piece and the neutral pieces on each square.
+pub const PIECE_KEYS: [[u64; 16]; 3] = piece_keys();
+
+/// Mixed in when player 2 is to move.
+pub const SIDE_KEY: u64 = splitmix64(48);
+
+/// Key for a player's score; scores are unbounded, so these are computed instead of looked up.
+pub fn score_key(player: u8, score: i32) -> u64 {
+    splitmix64(splitmix64(49 + player as u64) ^ score as u32 as u64)
+}
+
+/// Key for the number of turns left in a game with a turn limit.
+pub fn remaining_turns_key(remaining_turns: u32) -> u64 {
+    splitmix64(splitmix64(52) ^ remaining_turns as u64)
+}
@@ -0,0 +1,29 @@
+//! Runs the referee against the bot executable.
+
+use std::process::Command;
+
+fn bot() -> String {
+    format!("{} --strategy random --seed 1 --no-ponder", env!("CARGO_BIN_EXE_infinibattle-2022"))
+}
+
+/// The referee's last line, which announces the result.
+fn referee(args: &[&str]) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_referee")).args(args).output().unwrap();
+    assert!(output.status.success(), "{:?}", output);
+
+    String::from_utf8(output.stdout).unwrap().lines().last().unwrap().to_string()
+}
+
+#[test]
+fn bots_finish_a_game() {
+    let result = referee(&["--max-turns", "20", &bot(), &bot()]);
+
+    assert!(result.ends_with("is trapped") || result.contains("wins on score") || result.starts_with("draw"), "{}", result);
+}
+
+#[test]
+fn silent_bot_forfeits() {
+    let result = referee(&["--turn-timeout-ms", "200", &bot(), "sleep 10"]);
+
+    assert_eq!(result, "player 0 wins: player 1 timed out");
+}